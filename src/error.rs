@@ -0,0 +1,257 @@
+use std::fmt;
+
+use crate::octgn;
+use crate::FailedDownload;
+
+/// Everything that can go wrong in lotr-octgn. Each variant has its own process exit code, see
+/// `exit_code`.
+#[derive(Debug)]
+pub enum Error {
+    /// The OCTGN git repo couldn't be cloned or updated. Exit code 11.
+    Git(git2::Error),
+    /// A set, deck or game definition file couldn't be parsed. Exit code 1.
+    Xml(Box<dyn std::error::Error>),
+    /// A set or deck file was made for a different game than the game definition. Exit code 1.
+    WrongGame(octgn::NoMatchingGameError),
+    /// A request to Hall of Beorn or RingsDB failed. Exit code 5.
+    Http(reqwest::Error),
+    /// A RingsDB deck export couldn't be parsed. Exit code 7.
+    Json(serde_json::Error),
+    /// Cards or sets couldn't be matched to each other. Exit code 8.
+    Matching(Box<dyn std::error::Error>),
+    /// A file couldn't be read or written. Exit code 9.
+    Io(std::io::Error),
+    /// The `.o8c` image pack couldn't be built. Exit code 3.
+    Packaging(Box<dyn std::error::Error>),
+    /// Running offline and something needed isn't cached. Exit code 12.
+    Offline(NotCachedError),
+    /// One of our own files, the overrides file or pack history, couldn't be parsed. Exit code 13.
+    Config(Box<dyn std::error::Error>),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Xml(_) | Error::WrongGame(_) => 1,
+            Error::Packaging(_) => 3,
+            Error::Http(_) => 5,
+            Error::Json(_) => 7,
+            Error::Matching(_) => 8,
+            Error::Io(_) => 9,
+            Error::Git(_) => 11,
+            Error::Offline(_) => 12,
+            Error::Config(_) => 13,
+        }
+    }
+
+    /// This error followed by its source, for one-line warnings.
+    pub(crate) fn with_source(&self) -> String {
+        match std::error::Error::source(self) {
+            Some(source) => format!("{}: {}", self, source),
+            None => self.to_string(),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Git(err) => Some(err),
+            Error::Xml(err) => Some(err.as_ref()),
+            Error::WrongGame(err) => Some(err),
+            Error::Http(err) => Some(err),
+            Error::Json(err) => Some(err),
+            Error::Matching(err) => Some(err.as_ref()),
+            Error::Io(err) => Some(err),
+            Error::Packaging(err) => Some(err.as_ref()),
+            Error::Offline(err) => Some(err),
+            Error::Config(err) => Some(err.as_ref()),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Git(_) => write!(f, "Problem with the OCTGN git repo"),
+            Error::Xml(_) => write!(f, "Couldn't parse XML"),
+            Error::WrongGame(_) => write!(f, "Made for a different game"),
+            Error::Http(_) => write!(f, "HTTP request failed"),
+            Error::Json(_) => write!(f, "Couldn't parse the RingsDB deck"),
+            Error::Matching(_) => write!(f, "Couldn't match cards"),
+            Error::Io(_) => write!(f, "Couldn't read or write a file"),
+            Error::Packaging(_) => write!(f, "Couldn't build the image pack"),
+            Error::Offline(_) => write!(f, "Not available offline"),
+            Error::Config(_) => write!(f, "Couldn't parse the file"),
+        }
+    }
+}
+
+/// Some card images couldn't be downloaded. The pack was still written without them.
+#[derive(Debug)]
+pub struct DownloadsFailedError {
+    pub failed: Vec<FailedDownload>,
+}
+
+impl std::error::Error for DownloadsFailedError {}
+
+impl fmt::Display for DownloadsFailedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut ids = self
+            .failed
+            .iter()
+            .map(|download| download.id.as_str())
+            .collect::<Vec<&str>>();
+        ids.dedup();
+        write!(
+            f,
+            "Could not download {} images for cards: {}",
+            self.failed.len(),
+            ids.join(", ")
+        )
+    }
+}
+
+impl DownloadsFailedError {
+    pub fn new(failed: Vec<FailedDownload>) -> Self {
+        Self { failed }
+    }
+}
+
+/// OCTGN cards without a close enough Hall of Beorn card, when that's not allowed.
+#[derive(Debug)]
+pub struct UnmatchedCardsError {
+    /// The names of the cards.
+    pub cards: Vec<String>,
+}
+
+impl std::error::Error for UnmatchedCardsError {}
+
+impl fmt::Display for UnmatchedCardsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "No close enough Hall of Beorn card for {} cards: {}",
+            self.cards.len(),
+            self.cards.join(", ")
+        )
+    }
+}
+
+impl UnmatchedCardsError {
+    pub fn new(cards: Vec<String>) -> Self {
+        Self { cards }
+    }
+}
+
+/// Things that would have to be fetched, but we're offline.
+#[derive(Debug)]
+pub struct NotCachedError {
+    pub missing: Vec<String>,
+}
+
+impl std::error::Error for NotCachedError {}
+
+impl fmt::Display for NotCachedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} not in the cache: {}",
+            self.missing.len(),
+            self.missing.join(", ")
+        )
+    }
+}
+
+impl NotCachedError {
+    pub fn new(missing: Vec<String>) -> Self {
+        Self { missing }
+    }
+}
+
+impl From<git2::Error> for Error {
+    fn from(err: git2::Error) -> Self {
+        Error::Git(err)
+    }
+}
+
+impl From<roxmltree::Error> for Error {
+    fn from(err: roxmltree::Error) -> Self {
+        Error::Xml(Box::new(err))
+    }
+}
+
+/// A parse error from a set, deck or game definition file, keeping a game mismatch apart.
+impl From<Box<dyn std::error::Error>> for Error {
+    fn from(err: Box<dyn std::error::Error>) -> Self {
+        match err.downcast::<octgn::NoMatchingGameError>() {
+            Ok(err) => Error::WrongGame(*err),
+            Err(err) => Error::Xml(err),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Http(err)
+    }
+}
+
+impl From<octgn::CardsNotFoundError> for Error {
+    fn from(err: octgn::CardsNotFoundError) -> Self {
+        Error::Matching(Box::new(err))
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(err: zip::result::ZipError) -> Self {
+        Error::Packaging(Box::new(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::error::Error as _;
+
+    #[test]
+    fn test_source_chain() {
+        let err = Error::from(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "set.xml not found",
+        ));
+
+        assert_eq!(err.exit_code(), 9);
+        assert_eq!(err.to_string(), "Couldn't read or write a file");
+        assert_eq!(err.source().unwrap().to_string(), "set.xml not found");
+    }
+
+    #[test]
+    fn test_nested_source_chain() {
+        let card_error = octgn::CardParseError {
+            path: None,
+            line: 4,
+            column: 3,
+            set_name: "The Wilds of Rhovanion".to_string(),
+            card_id: None,
+            card_name: Some("Brand son of Bain".to_string()),
+            error: octgn::AttributeMissingError::new("card", "id"),
+        };
+        let err = Error::Xml(Box::new(card_error));
+
+        assert_eq!(err.exit_code(), 1);
+        let source = err.source().unwrap();
+        assert!(source.to_string().starts_with("4:3: "));
+        assert_eq!(
+            source.source().unwrap().to_string(),
+            "<card> is missing attribute 'id'."
+        );
+    }
+}
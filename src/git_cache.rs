@@ -2,20 +2,25 @@ use std::path::{Path, PathBuf};
 
 use git2::Repository;
 
+use crate::{Error, NotCachedError};
+
 pub struct GitCache<'a> {
     git_url: String,
     cache_dir: &'a Path,
     pub sets_dir: PathBuf,
+    pub definition_file: PathBuf,
 }
 
 impl<'a> GitCache<'a> {
     pub fn new(git_url: String, cache_dir: &'a Path) -> Self {
         let sets_dir = cache_dir.join("o8g").join("Sets");
+        let definition_file = cache_dir.join("o8g").join("definition.xml");
 
         GitCache {
             git_url,
             cache_dir,
             sets_dir,
+            definition_file,
         }
     }
 
@@ -32,14 +37,63 @@ impl<'a> GitCache<'a> {
         Ok(())
     }
 
-    pub fn update_or_fetch(&self) -> Result<(), Box<std::error::Error>> {
+    /// Bring the cached repo up to date, cloning it if it's missing or broken. When `offline`,
+    /// the cached repo is used as it is and has to exist already.
+    pub fn update_or_fetch(&self, offline: bool) -> Result<(), Error> {
+        if offline {
+            return Repository::open(self.cache_dir).map(|_| ()).map_err(|_| {
+                Error::Offline(NotCachedError::new(vec![format!(
+                    "OCTGN git repo at {}",
+                    self.cache_dir.display()
+                )]))
+            });
+        }
+
         self.update().or_else(|_err| {
-            fs_extra::dir::remove(&self.cache_dir)?;
+            fs_extra::dir::remove(&self.cache_dir).map_err(std::io::Error::other)?;
             Repository::clone(&self.git_url, &self.cache_dir)?;
 
             Ok(())
         })
     }
+
+    /// The commit the cached repo is at.
+    pub fn head(&self) -> Result<String, Error> {
+        let repo = Repository::open(self.cache_dir)?;
+        let commit = repo.head()?.peel_to_commit()?;
+
+        Ok(commit.id().to_string())
+    }
+
+    /// The set folders under `sets_dir` with anything added, changed or removed between
+    /// `commit` and the current head.
+    pub fn changed_set_dirs(&self, commit: &str) -> Result<Vec<PathBuf>, Error> {
+        let repo = Repository::open(self.cache_dir)?;
+        let old_tree = repo.revparse_single(commit)?.peel_to_tree()?;
+        let new_tree = repo.head()?.peel_to_tree()?;
+        // no DiffOptions, they can't be built safely with this version of git2, so the whole
+        // tree is diffed and anything outside sets_dir is skipped below
+        let diff = repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)?;
+        // diff paths are relative to the repo, and `new` always puts sets_dir inside it
+        let sets_prefix = self
+            .sets_dir
+            .strip_prefix(self.cache_dir)
+            .unwrap_or(&self.sets_dir);
+
+        let mut dirs = diff
+            .deltas()
+            .flat_map(|delta| vec![delta.old_file().path(), delta.new_file().path()])
+            .filter_map(|path| {
+                let set_dir = path?.strip_prefix(sets_prefix).ok()?.components().next()?;
+
+                Some(self.sets_dir.join(set_dir.as_os_str()))
+            })
+            .collect::<Vec<PathBuf>>();
+        dirs.sort();
+        dirs.dedup();
+
+        Ok(dirs)
+    }
 }
 
 #[cfg(test)]
@@ -57,7 +111,7 @@ mod tests {
         let tmp_dir = TempDir::new("octgn").unwrap();
 
         let git_cache = GitCache::new(GIT_URL.to_string(), &tmp_dir.path());
-        let result = git_cache.update_or_fetch();
+        let result = git_cache.update_or_fetch(false);
         assert!(result.is_ok());
         assert!(tmp_dir.path().join("LotR set editor.xlsm").exists());
     }
@@ -121,7 +175,7 @@ mod tests {
             )
             .unwrap();
 
-        let result = git_cache.update_or_fetch();
+        let result = git_cache.update_or_fetch(false);
         assert!(result.is_ok());
         assert!(cache_git_dir.join("new_file.txt").exists());
     }
@@ -133,8 +187,75 @@ mod tests {
         repo.remote_delete("origin").unwrap();
         let git_cache = GitCache::new(GIT_URL.to_string(), &tmp_dir.path());
 
-        let result = git_cache.update_or_fetch();
+        let result = git_cache.update_or_fetch(false);
         assert!(result.is_ok());
         assert!(tmp_dir.path().join("LotR set editor.xlsm").exists());
     }
+
+    #[test]
+    fn test_update_or_fetch_offline() {
+        let tmp_dir = TempDir::new("octgn").unwrap();
+        let cache_git_dir = tmp_dir.path().join("cache");
+        let git_cache = GitCache::new(GIT_URL.to_string(), &cache_git_dir);
+
+        match git_cache.update_or_fetch(true) {
+            Err(Error::Offline(err)) => assert_eq!(err.missing.len(), 1),
+            _ => panic!("expected the repo to be missing from the cache"),
+        }
+        assert!(!cache_git_dir.exists());
+
+        Repository::init(&cache_git_dir).unwrap();
+        assert!(git_cache.update_or_fetch(true).is_ok());
+    }
+
+    #[test]
+    fn test_changed_set_dirs() {
+        let tmp_dir = TempDir::new("octgn").unwrap();
+        let repo = Repository::init(tmp_dir.path()).unwrap();
+        let sig = git2::Signature::now("lotr-octgn", "lotr-octgn@example.com").unwrap();
+        let commit = |paths: &[&str], message: &str| {
+            let mut index = repo.index().unwrap();
+            for path in paths {
+                let file_path = tmp_dir.path().join(path);
+                std::fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+                std::fs::write(&file_path, message).unwrap();
+                index.add_path(Path::new(path)).unwrap();
+            }
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+            let parents = parent.iter().collect::<Vec<_>>();
+            index.write().unwrap();
+
+            repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents)
+                .unwrap()
+                .to_string()
+        };
+        let first = commit(
+            &[
+                "o8g/Sets/core/set.xml",
+                "o8g/Sets/hunt/set.xml",
+                "o8g/Sets/carrock/set.xml",
+            ],
+            "First",
+        );
+        commit(
+            &[
+                "o8g/Sets/hunt/set.xml",
+                "o8g/Sets/carrock/Markers/troll.png",
+                "o8g/definition.xml",
+            ],
+            "Second",
+        );
+        let git_cache = GitCache::new(GIT_URL.to_string(), tmp_dir.path());
+
+        let head = git_cache.head().unwrap();
+        assert!(git_cache.changed_set_dirs(&head).unwrap().is_empty());
+        assert_eq!(
+            git_cache.changed_set_dirs(&first).unwrap(),
+            vec![
+                git_cache.sets_dir.join("carrock"),
+                git_cache.sets_dir.join("hunt")
+            ]
+        );
+    }
 }
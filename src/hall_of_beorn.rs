@@ -1,5 +1,11 @@
 use serde_derive::*;
 
+use crate::Error;
+
+mod cache;
+
+pub use self::cache::Cache;
+
 #[cfg(not(test))]
 const HOB_URL: &str = "http://hallofbeorn.com";
 #[cfg(test)]
@@ -16,6 +22,7 @@ pub struct Card {
     pub front: Side,
     pub back: Option<Side>,
     pub card_set: String,
+    pub encounter_info: Option<EncounterInfo>,
     pub number: u32,
     pub quantity: u32,
     pub artist: String,
@@ -24,13 +31,21 @@ pub struct Card {
 }
 
 impl Card {
-    pub fn fetch_all(set_name: &str) -> Result<Vec<Card>, reqwest::Error> {
-        let cards: Vec<Card> = reqwest::Client::new()
-            .get(&format!("{}/Export/Search?CardSet={}", HOB_URL, set_name))
-            .send()?
-            .json()?;
-
-        Ok(cards)
+    /// Every card in a set, going through `cache` when there is one.
+    pub fn fetch_all(set_name: &str, cache: Option<&Cache>) -> Result<Vec<Card>, Error> {
+        let fetch = || {
+            let cards: Vec<Card> = reqwest::Client::new()
+                .get(&format!("{}/Export/Search?CardSet={}", HOB_URL, set_name))
+                .send()?
+                .json()?;
+
+            Ok(cards)
+        };
+
+        match cache {
+            Some(cache) => cache.fetch(&format!("cards in set '{}'", set_name), fetch),
+            None => fetch(),
+        }
     }
 }
 
@@ -43,6 +58,17 @@ pub struct Stats {
     pub attack: Option<String>,
     pub defense: Option<String>,
     pub hit_points: Option<String>,
+    pub engagement_cost: Option<String>,
+    pub threat: Option<String>,
+    pub quest_points: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EncounterInfo {
+    pub encounter_set: String,
+    pub stage_number: Option<u32>,
+    pub stage_letter: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -54,6 +80,7 @@ pub struct Side {
     pub traits: Vec<String>,
     pub keywords: Vec<String>,
     pub text: Vec<String>,
+    pub shadow: Option<String>,
     pub flavor_text: Option<String>,
 }
 
@@ -66,13 +93,21 @@ pub struct CardSet {
 }
 
 impl CardSet {
-    pub fn fetch_all() -> Result<Vec<CardSet>, reqwest::Error> {
-        let card_sets = reqwest::Client::new()
-            .get(&format!("{}/Export/CardSets", HOB_URL))
-            .send()?
-            .json()?;
-
-        Ok(card_sets)
+    /// Every set, going through `cache` when there is one.
+    pub fn fetch_all(cache: Option<&Cache>) -> Result<Vec<CardSet>, Error> {
+        let fetch = || {
+            let card_sets: Vec<CardSet> = reqwest::Client::new()
+                .get(&format!("{}/Export/CardSets", HOB_URL))
+                .send()?
+                .json()?;
+
+            Ok(card_sets)
+        };
+
+        match cache {
+            Some(cache) => cache.fetch("card sets", fetch),
+            None => fetch(),
+        }
     }
 }
 
@@ -82,12 +117,14 @@ mod tests {
 
     use crate::tests::mocks::hall_of_beorn as mocks;
 
+    use tempdir::TempDir;
+
     #[test]
     fn test_card_fetch_all() {
         let set = "The Wilds of Rhovanion";
         let _m = mocks::card_set(&set);
 
-        let result = Card::fetch_all(set);
+        let result = Card::fetch_all(set, None);
         assert!(result.is_ok());
 
         let cards = result.unwrap();
@@ -97,10 +134,35 @@ mod tests {
     #[test]
     fn test_card_sets_fetch_all() {
         let _m = mocks::card_sets();
-        let result = CardSet::fetch_all();
+        let result = CardSet::fetch_all(None);
         assert!(result.is_ok());
 
         let card_sets = result.unwrap();
         assert_eq!(card_sets.len(), 144);
     }
+
+    #[test]
+    fn test_fetch_all_offline() {
+        let tmp_dir = TempDir::new("hob").unwrap();
+        let mut cache = Cache::new(tmp_dir.path().to_path_buf());
+        let set = "The Wilds of Rhovanion";
+
+        cache.offline = true;
+        match Card::fetch_all(set, Some(&cache)) {
+            Err(Error::Offline(err)) => {
+                assert_eq!(err.missing, vec!["cards in set 'The Wilds of Rhovanion'"])
+            }
+            _ => panic!("expected the set to be missing from the cache"),
+        }
+
+        cache.offline = false;
+        {
+            let _m = mocks::card_set(set);
+            assert_eq!(Card::fetch_all(set, Some(&cache)).unwrap().len(), 80);
+        }
+
+        cache.offline = true;
+        assert_eq!(Card::fetch_all(set, Some(&cache)).unwrap().len(), 80);
+        assert!(CardSet::fetch_all(Some(&cache)).is_err());
+    }
 }
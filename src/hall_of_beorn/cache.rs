@@ -0,0 +1,189 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_derive::*;
+
+use crate::{now, Error, NotCachedError};
+
+/// The shape of cached responses. Bump it whenever `Card` or `CardSet` change, so responses
+/// cached by older versions are fetched again instead of failing to parse.
+pub const CACHE_VERSION: u32 = 1;
+
+/// A cached response, stored as JSON along with what it was a response to.
+#[derive(Deserialize, Serialize)]
+struct Entry<T> {
+    version: u32,
+    key: String,
+    /// When the response was fetched, in seconds since the Unix epoch.
+    fetched_at: u64,
+    data: T,
+}
+
+/// Hall of Beorn responses saved by earlier runs, keyed by the SHA-1 of what was requested.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Cache {
+    pub dir: PathBuf,
+    /// How long a cached response is used before it's fetched again.
+    pub ttl: Duration,
+    /// Fetch everything again, however recently it was cached.
+    pub refresh: bool,
+    /// Only use cached responses, never the network. Wins over `ttl` and `refresh`.
+    pub offline: bool,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf) -> Self {
+        Cache {
+            dir,
+            ttl: Duration::from_secs(60 * 60 * 24),
+            refresh: false,
+            offline: false,
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir
+            .join(format!("{}.json", sha1::Sha1::from(key).digest()))
+    }
+
+    /// The cached response for `key` if it's recent enough, otherwise a fresh one from `fetch`
+    /// which is then cached.
+    pub(crate) fn fetch<T, F>(&self, key: &str, fetch: F) -> Result<T, Error>
+    where
+        T: DeserializeOwned + Serialize,
+        F: FnOnce() -> Result<T, Error>,
+    {
+        if self.offline {
+            return self
+                .read(key)
+                .map(|entry| entry.data)
+                .ok_or_else(|| Error::Offline(NotCachedError::new(vec![key.to_string()])));
+        }
+        if !self.refresh {
+            let cutoff = now().saturating_sub(self.ttl.as_secs());
+            if let Some(entry) = self.read(key).filter(|entry| entry.fetched_at >= cutoff) {
+                return Ok(entry.data);
+            }
+        }
+
+        let data = fetch()?;
+        // the data is still good even if it can't be cached for next time
+        if let Err(err) = self.write(key, &data) {
+            println!(
+                "Warning: Couldn't cache the Hall of Beorn response for '{}': {}",
+                key,
+                err.with_source()
+            );
+        }
+
+        Ok(data)
+    }
+
+    /// The cached entry for `key`, unless it's missing or from another `CACHE_VERSION`.
+    fn read<T: DeserializeOwned>(&self, key: &str) -> Option<Entry<T>> {
+        let file = File::open(self.path(key)).ok()?;
+        let entry: Entry<T> = serde_json::from_reader(file).ok()?;
+
+        Some(entry).filter(|entry| entry.version == CACHE_VERSION)
+    }
+
+    fn write<T: Serialize>(&self, key: &str, data: &T) -> Result<(), Error> {
+        std::fs::create_dir_all(&self.dir)?;
+        let entry = Entry {
+            version: CACHE_VERSION,
+            key: key.to_string(),
+            fetched_at: now(),
+            data,
+        };
+        let mut file = File::create(self.path(key))?;
+        let json = serde_json::to_string(&entry).map_err(std::io::Error::from)?;
+        file.write_all(json.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+
+    use tempdir::TempDir;
+
+    const KEY: &str = "card sets";
+
+    /// Fetch `KEY` through `cache`, returning what came back and whether it was fetched.
+    fn fetch(cache: &Cache, fresh: &str) -> (String, bool) {
+        let fetched = Cell::new(false);
+        let data = cache
+            .fetch(KEY, || {
+                fetched.set(true);
+                Ok(fresh.to_string())
+            })
+            .unwrap();
+
+        (data, fetched.get())
+    }
+
+    #[test]
+    fn test_fetch_ttl() {
+        let tmp_dir = TempDir::new("hob").unwrap();
+        let mut cache = Cache::new(tmp_dir.path().join("hob"));
+
+        assert_eq!(fetch(&cache, "first"), ("first".to_string(), true));
+        assert_eq!(fetch(&cache, "second"), ("first".to_string(), false));
+
+        cache.refresh = true;
+        assert_eq!(fetch(&cache, "third"), ("third".to_string(), true));
+
+        cache.refresh = false;
+        let mut entry: Entry<String> = cache.read(KEY).unwrap();
+        entry.fetched_at -= 60 * 60 * 25;
+        std::fs::write(
+            cache.path(KEY),
+            serde_json::to_string(&entry).unwrap().as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(fetch(&cache, "fourth"), ("fourth".to_string(), true));
+
+        cache.ttl = Duration::from_secs(0);
+        cache.offline = true;
+        assert_eq!(fetch(&cache, "fifth"), ("fourth".to_string(), false));
+    }
+
+    #[test]
+    fn test_fetch_unwritable() {
+        let tmp_dir = TempDir::new("hob").unwrap();
+        // a file where the cache directory should be
+        let dir = tmp_dir.path().join("hob");
+        std::fs::write(&dir, "").unwrap();
+        let cache = Cache::new(dir);
+
+        assert_eq!(fetch(&cache, "fresh"), ("fresh".to_string(), true));
+    }
+
+    #[test]
+    fn test_fetch_other_version() {
+        let tmp_dir = TempDir::new("hob").unwrap();
+        let cache = Cache::new(tmp_dir.path().to_path_buf());
+        let old_entry = Entry {
+            version: CACHE_VERSION - 1,
+            key: KEY.to_string(),
+            fetched_at: now(),
+            data: "old".to_string(),
+        };
+        std::fs::write(
+            cache.path(KEY),
+            serde_json::to_string(&old_entry).unwrap().as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(fetch(&cache, "new"), ("new".to_string(), true));
+        assert_eq!(cache.read::<String>(KEY).unwrap().version, CACHE_VERSION);
+    }
+}
@@ -0,0 +1,274 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde_derive::*;
+use uuid::Uuid;
+
+use crate::{now, unix_time, Error};
+
+/// What we know about a cached image, stored as JSON next to it.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CacheEntry {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// When the image was last downloaded or revalidated, in seconds since the Unix epoch.
+    pub used_at: u64,
+}
+
+/// Card images downloaded by earlier packs, keyed by the SHA-1 of their URL.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ImageCache {
+    pub dir: PathBuf,
+}
+
+impl ImageCache {
+    pub fn new(dir: PathBuf) -> Self {
+        ImageCache { dir }
+    }
+
+    fn key(url: &str) -> String {
+        sha1::Sha1::from(url).digest().to_string()
+    }
+
+    /// Where the image for `url` is, or would be, cached.
+    pub fn path(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{}.jpg", ImageCache::key(url)))
+    }
+
+    fn entry_path(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", ImageCache::key(url)))
+    }
+
+    /// The cache entry for `url`, if both it and its image exist.
+    pub fn entry(&self, url: &str) -> Option<CacheEntry> {
+        if !self.path(url).is_file() {
+            return None;
+        }
+
+        read_entry(&self.entry_path(url)).ok()
+    }
+
+    /// Save the image for `url` along with the validators the server sent for it.
+    pub fn store<R: Read>(
+        &self,
+        url: &str,
+        image: &mut R,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    ) -> Result<(), Error> {
+        std::fs::create_dir_all(&self.dir)?;
+        // write to a temporary file first, so an interrupted download never looks cached, with a
+        // name of its own so concurrent downloads of the same URL don't write over each other
+        let tmp_path = self
+            .dir
+            .join(format!("{}.{}.part", ImageCache::key(url), Uuid::new_v4()));
+        let mut file = File::create(&tmp_path)?;
+        std::io::copy(image, &mut file).inspect_err(|_| {
+            let _ = std::fs::remove_file(&tmp_path);
+        })?;
+        std::fs::rename(&tmp_path, self.path(url))?;
+
+        self.write_entry(&CacheEntry {
+            url: url.to_string(),
+            etag,
+            last_modified,
+            used_at: now(),
+        })
+    }
+
+    /// Mark the image for `url` as still current.
+    pub fn touch(&self, url: &str) -> Result<(), Error> {
+        let mut entry = read_entry(&self.entry_path(url))?;
+        entry.used_at = now();
+
+        self.write_entry(&entry)
+    }
+
+    fn write_entry(&self, entry: &CacheEntry) -> Result<(), Error> {
+        let mut file = File::create(self.entry_path(&entry.url))?;
+        let json = serde_json::to_string(entry).map_err(std::io::Error::from)?;
+        file.write_all(json.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Every cached image with its size in bytes, oldest first.
+    pub fn entries(&self) -> Result<Vec<(CacheEntry, u64)>, Error> {
+        if !self.dir.is_dir() {
+            return Ok(vec![]);
+        }
+
+        let mut entries = std::fs::read_dir(&self.dir)?
+            .filter_map(|dir_entry| dir_entry.ok())
+            .map(|dir_entry| dir_entry.path())
+            .filter(|path| extension(path) == Some("json"))
+            .filter_map(|path| {
+                let entry = read_entry(&path).ok()?;
+                let size = std::fs::metadata(path.with_extension("jpg")).ok()?.len();
+
+                Some((entry, size))
+            })
+            .collect::<Vec<(CacheEntry, u64)>>();
+        entries.sort_by_key(|(entry, _)| entry.used_at);
+
+        Ok(entries)
+    }
+
+    /// Remove images not used within `max_age`, and any files that aren't part of a complete
+    /// entry. Downloads still in progress are left alone unless they're older than `max_age`.
+    /// Returns how many files were removed.
+    pub fn prune(&self, max_age: Duration) -> Result<usize, Error> {
+        if !self.dir.is_dir() {
+            return Ok(0);
+        }
+
+        let cutoff = now().saturating_sub(max_age.as_secs());
+        let mut removed = 0;
+        for dir_entry in std::fs::read_dir(&self.dir)? {
+            let dir_entry = dir_entry?;
+            let path = dir_entry.path();
+            if extension(&path) == Some("part") {
+                if unix_time(dir_entry.metadata()?.modified()?) < cutoff {
+                    std::fs::remove_file(&path)?;
+                    removed += 1;
+                }
+                continue;
+            }
+            let entry_path = path.with_extension("json");
+            let image_path = path.with_extension("jpg");
+            let keep = match read_entry(&entry_path) {
+                Ok(entry) => image_path.is_file() && entry.used_at >= cutoff,
+                Err(_) => false,
+            };
+            let cache_file = matches!(extension(&path), Some("json") | Some("jpg"));
+            if !keep || !cache_file {
+                std::fs::remove_file(&path)?;
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    pub fn clear(&self) -> Result<(), Error> {
+        if self.dir.is_dir() {
+            std::fs::remove_dir_all(&self.dir)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn read_entry(path: &Path) -> Result<CacheEntry, Error> {
+    let file = File::open(path)?;
+
+    serde_json::from_reader(file).map_err(|err| Error::Config(Box::new(err)))
+}
+
+fn extension(path: &Path) -> Option<&str> {
+    path.extension().and_then(|extension| extension.to_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempdir::TempDir;
+
+    const URL: &str = "http://hallofbeorn.com/Images/Cards/Brand-son-of-Bain.jpg";
+
+    #[test]
+    fn test_store() {
+        let tmp_dir = TempDir::new("images").unwrap();
+        let cache = ImageCache::new(tmp_dir.path().join("images"));
+        assert!(cache.entry(URL).is_none());
+
+        cache
+            .store(
+                URL,
+                &mut "Brand son of Bain".as_bytes(),
+                Some("\"abc\"".to_string()),
+                None,
+            )
+            .unwrap();
+
+        let entry = cache.entry(URL).unwrap();
+        assert_eq!(&entry.url, URL);
+        assert_eq!(entry.etag.as_ref().unwrap(), "\"abc\"");
+        assert_eq!(
+            std::fs::read_to_string(cache.path(URL)).unwrap(),
+            "Brand son of Bain"
+        );
+
+        let entries = cache.entries().unwrap();
+        assert_eq!(entries, vec![(entry, 17)]);
+    }
+
+    #[test]
+    fn test_prune() {
+        let tmp_dir = TempDir::new("images").unwrap();
+        let cache = ImageCache::new(tmp_dir.path().to_path_buf());
+        let old_url = "http://hallofbeorn.com/Images/Cards/Old.jpg";
+        cache.store(URL, &mut "new".as_bytes(), None, None).unwrap();
+        cache
+            .store(old_url, &mut "old".as_bytes(), None, None)
+            .unwrap();
+        let mut old_entry = cache.entry(old_url).unwrap();
+        old_entry.used_at -= 60 * 60 * 24 * 31;
+        cache.write_entry(&old_entry).unwrap();
+        // an image whose entry was lost
+        std::fs::write(tmp_dir.path().join("orphan.jpg"), "orphan").unwrap();
+        // a download that's still being written
+        let part_path = tmp_dir.path().join("download.part");
+        std::fs::write(&part_path, "partial").unwrap();
+
+        let removed = cache.prune(Duration::from_secs(60 * 60 * 24 * 30)).unwrap();
+        assert_eq!(removed, 3);
+        assert!(cache.entry(URL).is_some());
+        assert!(cache.entry(old_url).is_none());
+        assert!(!cache.path(old_url).exists());
+        assert!(part_path.exists());
+    }
+
+    #[test]
+    fn test_store_concurrently() {
+        let tmp_dir = TempDir::new("images").unwrap();
+        let cache = ImageCache::new(tmp_dir.path().to_path_buf());
+
+        let stores = (0..8)
+            .map(|_| {
+                let cache = cache.clone();
+                std::thread::spawn(move || {
+                    cache
+                        .store(URL, &mut "Brand son of Bain".as_bytes(), None, None)
+                        .is_ok()
+                })
+            })
+            .collect::<Vec<_>>();
+        for store in stores {
+            assert!(store.join().unwrap());
+        }
+
+        assert_eq!(
+            std::fs::read_to_string(cache.path(URL)).unwrap(),
+            "Brand son of Bain"
+        );
+        assert_eq!(cache.entries().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_clear() {
+        let tmp_dir = TempDir::new("images").unwrap();
+        let cache = ImageCache::new(tmp_dir.path().join("images"));
+        cache
+            .store(URL, &mut "Brand son of Bain".as_bytes(), None, None)
+            .unwrap();
+
+        cache.clear().unwrap();
+        assert!(!cache.dir.exists());
+        assert!(cache.entries().unwrap().is_empty());
+    }
+}
@@ -1,20 +1,45 @@
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use indicatif::ProgressBar;
+use rand::Rng;
 use rayon::prelude::*;
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use reqwest::StatusCode;
 use tempdir::TempDir;
+use uuid::Uuid;
 use walkdir::WalkDir;
 
+mod error;
 mod git_cache;
 pub mod hall_of_beorn;
+mod image_cache;
+mod match_report;
+mod matching;
 pub mod octgn;
+mod overrides;
+mod pack_history;
+pub mod ringsdb;
 
+pub use crate::error::{DownloadsFailedError, Error, NotCachedError, UnmatchedCardsError};
 pub use crate::git_cache::GitCache;
+pub use crate::image_cache::{CacheEntry, ImageCache};
+pub use crate::match_report::{CardReport, MatchReport, UnusedCard};
+pub use crate::matching::{
+    match_cards, normalize_title, CardMatch, MatchOptions, MatchStrategy, UnmatchedAction,
+};
+pub use crate::overrides::{CardOverride, Overrides};
+pub use crate::pack_history::{PackHistory, PackRecord};
 
-const MAX_SET_LEVENSHTEIN: usize = 5;
+/// Set names further apart than this once normalized aren't the same set.
+const MAX_SET_LEVENSHTEIN: usize = 3;
+/// How far an image's aspect ratio can be from its card size's before we warn about it.
+const MAX_ASPECT_DIFFERENCE: f64 = 0.1;
 pub const OCTGN_GIT_URL: &str = "https://github.com/GeckoTH/Lord-of-the-Rings.git";
 
 struct CardDownload {
@@ -23,99 +48,489 @@ struct CardDownload {
     back_url: Option<String>,
 }
 
-fn octgn_to_hob<'a>(
-    octgn_card_name: &str,
-    hob_map: &'a HashMap<&str, &hall_of_beorn::Card>,
-    hob_cards: &'a [hall_of_beorn::Card],
-) -> &'a hall_of_beorn::Card {
-    match hob_map.get(&octgn_card_name) {
-        Some(hob_card) => hob_card,
-        None => {
-            let hob_card = guess_hob_card(hob_cards, &octgn_card_name);
-            println!(
-                "Warning: Could not find OCTGN Card '{}', using Hall of Beorn Card '{}' instead.",
-                &octgn_card_name, hob_card.title
-            );
+/// How card images are downloaded.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DownloadOptions {
+    /// The most images downloaded at once. 0 is treated as 1.
+    pub max_concurrent: usize,
+    /// How long a single request can take before it's abandoned.
+    pub timeout: Duration,
+    /// How many times to retry a request after a timeout, a 5xx or a 429.
+    pub retries: u32,
+    /// The delay before the first retry, doubled for each retry after that.
+    pub backoff: Duration,
+    /// Where to keep images between packs. Without one every image is downloaded every time.
+    pub cache: Option<ImageCache>,
+    /// Only use images already in `cache`, never the network.
+    pub offline: bool,
+}
 
-            hob_card
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        DownloadOptions {
+            max_concurrent: 8,
+            timeout: Duration::from_secs(30),
+            retries: 3,
+            backoff: Duration::from_millis(500),
+            cache: None,
+            offline: false,
+        }
+    }
+}
+
+impl DownloadOptions {
+    /// The delay before retry number `attempt` (starting at 0), with up to as much again added
+    /// as jitter so throttled downloads don't all retry at once.
+    fn backoff_delay(&self, attempt: u32) -> Duration {
+        let delay = self.backoff * 2u32.saturating_pow(attempt);
+        let jitter_ms = rand::thread_rng().gen_range(0, delay.as_millis() as u64 + 1);
+
+        delay + Duration::from_millis(jitter_ms)
+    }
+}
+
+/// A card image that couldn't be downloaded.
+#[derive(Debug, Eq, PartialEq)]
+pub struct FailedDownload {
+    pub id: String,
+    pub url: String,
+    pub reason: String,
+}
+
+impl FailedDownload {
+    fn new(id: &str, url: &str, err: &Error) -> Self {
+        FailedDownload {
+            id: id.to_string(),
+            url: url.to_string(),
+            reason: err.with_source(),
         }
     }
 }
 
 fn get_image_urls(
+    set_name: &str,
     octgn_cards: &[octgn::Card],
     hob_cards: &[hall_of_beorn::Card],
-) -> Vec<CardDownload> {
-    let hob_map = hob_cards.iter().fold(HashMap::new(), |mut acc, card| {
-        acc.insert(card.title.as_str(), card);
+    match_options: &MatchOptions,
+) -> Result<Vec<CardDownload>, Error> {
+    let matches = match_cards(set_name, octgn_cards, hob_cards, match_options);
+    let mut counts = HashMap::new();
+    for card_match in &matches {
+        *counts.entry(card_match.strategy).or_insert(0) += 1;
+        let hob_title = card_match.hob_card.map_or("", |hob_card| &hob_card.title);
+        match card_match.strategy {
+            MatchStrategy::Number if card_match.distance > 0 => println!(
+                "Warning: OCTGN Card '{}' has the number of Hall of Beorn Card '{}', but a different title.",
+                &card_match.octgn_name, hob_title
+            ),
+            MatchStrategy::Fuzzy => println!(
+                "Warning: Could not find OCTGN Card '{}', using Hall of Beorn Card '{}' instead.",
+                &card_match.octgn_name, hob_title
+            ),
+            MatchStrategy::Unmatched => println!(
+                "Warning: No Hall of Beorn Card is close enough to OCTGN Card '{}', the closest is '{}'.",
+                &card_match.octgn_name, hob_title
+            ),
+            _ => (),
+        }
+    }
+    println!(
+        "Matched {} cards by override, {} by number, {} by title and {} by closest title, {} unmatched",
+        counts.get(&MatchStrategy::Override).unwrap_or(&0),
+        counts.get(&MatchStrategy::Number).unwrap_or(&0),
+        counts.get(&MatchStrategy::Title).unwrap_or(&0),
+        counts.get(&MatchStrategy::Fuzzy).unwrap_or(&0),
+        counts.get(&MatchStrategy::Unmatched).unwrap_or(&0)
+    );
 
-        acc
-    });
+    let unmatched = matches
+        .iter()
+        .filter(|card_match| card_match.matched().is_none())
+        .map(|card_match| card_match.octgn_name.to_string())
+        .collect::<Vec<String>>();
+    if !unmatched.is_empty() && match_options.unmatched == UnmatchedAction::Fail {
+        return Err(Error::Matching(Box::new(UnmatchedCardsError::new(
+            unmatched,
+        ))));
+    }
+    let placeholder = match &match_options.unmatched {
+        UnmatchedAction::Placeholder(path) => Some(path.to_string_lossy().to_string()),
+        _ => None,
+    };
+    let image_url = |card_match: &CardMatch| card_match.image_url().or_else(|| placeholder.clone());
 
-    octgn_cards
-        .par_iter()
-        .map(|octgn_card| {
-            let hob_card = octgn_to_hob(&octgn_card.name, &hob_map, &hob_cards);
-            let back_url = octgn_card.back_name.as_ref().map(|back_name| {
-                if back_name == &octgn_card.name {
+    let by_side = matches
+        .iter()
+        .map(|card_match| ((card_match.octgn_id.as_str(), card_match.back), card_match))
+        .collect::<HashMap<(&str, bool), &CardMatch>>();
+
+    let card_downloads = octgn_cards
+        .iter()
+        .filter_map(|octgn_card| {
+            let front = by_side.get(&(octgn_card.id.as_str(), false))?;
+            let front_url = image_url(front)?;
+            let back_url = octgn_card.back().and_then(|back| {
+                if octgn_card.is_same_card(back) {
                     // replace A side to B side
-                    hob_card.front.image_path.replace("A.jpg", "B.jpg")
+                    match (front.matched(), front.strategy) {
+                        (_, MatchStrategy::Unmatched) => placeholder.clone(),
+                        (Some(hob_card), _) => {
+                            Some(hob_card.front.image_path.replace("A.jpg", "B.jpg"))
+                        }
+                        // an override image, which doesn't say what its back is
+                        (None, _) => None,
+                    }
                 } else {
-                    // if the back has a different name, then fetch that card from Hall of Beorn
-                    octgn_to_hob(&back_name, &hob_map, &hob_cards)
-                        .front
-                        .image_path
-                        .to_owned()
+                    // if the back is a different card, then fetch that card from Hall of Beorn
+                    by_side
+                        .get(&(octgn_card.id.as_str(), true))
+                        .and_then(|card_match| image_url(card_match))
                 }
             });
 
-            CardDownload {
+            Some(CardDownload {
                 id: octgn_card.id.to_string(),
-                front_url: hob_card.front.image_path.to_owned(),
+                front_url,
                 back_url,
-            }
+            })
         })
-        .collect()
+        .collect();
+
+    Ok(card_downloads)
+}
+
+/// `time` in seconds since the Unix epoch, the way the caches store it.
+pub(crate) fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+pub(crate) fn now() -> u64 {
+    unix_time(SystemTime::now())
+}
+
+/// Whether an image is downloaded, rather than copied from a local file.
+fn is_remote(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Whether a failed request might succeed if it's tried again.
+fn is_transient(err: &reqwest::Error) -> bool {
+    if let Some(status) = err.status() {
+        return status.is_server_error() || status.as_u16() == 429;
+    }
+
+    match err.get_ref() {
+        // reqwest reports timeouts as IO errors
+        Some(inner) if inner.is::<std::io::Error>() => true,
+        Some(inner) => match inner.downcast_ref::<hyper::Error>() {
+            Some(hyper_err) => {
+                hyper_err.is_closed()
+                    || hyper_err
+                        .cause2()
+                        .is_some_and(|cause| cause.is::<std::io::Error>())
+            }
+            // invalid URLs, headers and redirect loops won't get any better
+            None => false,
+        },
+        None => false,
+    }
+}
+
+fn get(
+    client: &reqwest::Client,
+    url: &str,
+    headers: &HeaderMap,
+    options: &DownloadOptions,
+) -> Result<reqwest::Response, Error> {
+    let mut attempt = 0;
+    loop {
+        let resp = client.get(url).headers(headers.clone()).send();
+        match resp.and_then(|resp| resp.error_for_status()) {
+            Ok(resp) => return Ok(resp),
+            Err(ref err) if attempt < options.retries && is_transient(err) => {
+                std::thread::sleep(options.backoff_delay(attempt));
+                attempt += 1;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
 }
 
+fn download(
+    client: &reqwest::Client,
+    url: &str,
+    file_path: &Path,
+    options: &DownloadOptions,
+) -> Result<(), Error> {
+    if !is_remote(url) {
+        std::fs::copy(url, file_path)?;
+        return Ok(());
+    }
+    if let Some(cache) = &options.cache {
+        if options.offline {
+            std::fs::copy(cache.path(url), file_path)?;
+            return Ok(());
+        }
+        return download_cached(client, cache, url, file_path, options);
+    }
+
+    let mut resp = get(client, url, &HeaderMap::new(), options)?;
+    let mut file = File::create(file_path)?;
+    std::io::copy(&mut resp, &mut file).inspect_err(|_| {
+        // don't leave a truncated image behind
+        let _ = std::fs::remove_file(file_path);
+    })?;
+
+    Ok(())
+}
+
+fn header(resp: &reqwest::Response, name: HeaderName) -> Option<String> {
+    resp.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+}
+
+/// Download `url` into the cache unless the cached copy is still current, then copy it to
+/// `file_path`.
+fn download_cached(
+    client: &reqwest::Client,
+    cache: &ImageCache,
+    url: &str,
+    file_path: &Path,
+    options: &DownloadOptions,
+) -> Result<(), Error> {
+    let mut headers = HeaderMap::new();
+    if let Some(entry) = cache.entry(url) {
+        let validators = vec![
+            (IF_NONE_MATCH, entry.etag),
+            (IF_MODIFIED_SINCE, entry.last_modified),
+        ];
+        for (name, value) in validators {
+            if let Some(value) = value.and_then(|value| HeaderValue::from_str(&value).ok()) {
+                headers.insert(name, value);
+            }
+        }
+    }
+
+    let mut resp = get(client, url, &headers, options)?;
+    if resp.status() == StatusCode::NOT_MODIFIED {
+        cache.touch(url)?;
+    } else {
+        let etag = header(&resp, ETAG);
+        let last_modified = header(&resp, LAST_MODIFIED);
+        cache.store(url, &mut resp, etag, last_modified)?;
+    }
+    std::fs::copy(cache.path(url), file_path)?;
+
+    Ok(())
+}
+
+/// Where a set's card images go in an image pack.
+fn cards_dir(work_dir: &Path, game: &octgn::Game, set_id: &str) -> PathBuf {
+    work_dir
+        .join(game.id())
+        .join("Sets")
+        .join(set_id)
+        .join("Cards")
+}
+
+/// The width and height of a PNG or JPEG image, read from its header.
+fn image_dimensions(image: &[u8]) -> Option<(u32, u32)> {
+    let read_u16 = |at: usize| {
+        image
+            .get(at..at + 2)
+            .map(|bytes| u32::from(bytes[0]) << 8 | u32::from(bytes[1]))
+    };
+
+    if image.starts_with(b"\x89PNG\r\n\x1a\n") {
+        // the IHDR chunk always comes first, and no image is 65536 pixels across
+        return Some((read_u16(18)?, read_u16(22)?));
+    }
+    if !image.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+
+    // skip segments until the start of frame, which holds the dimensions
+    let mut at = 2;
+    loop {
+        if *image.get(at)? != 0xFF {
+            return None;
+        }
+        match *image.get(at + 1)? {
+            0xC4 | 0xC8 | 0xCC => (),
+            0xC0..=0xCF => return Some((read_u16(at + 7)?, read_u16(at + 5)?)),
+            _ => (),
+        }
+        at += 2 + read_u16(at + 2)? as usize;
+    }
+}
+
+/// Warn about downloaded images whose shape doesn't fit the size OCTGN draws the card at, like a
+/// portrait image for a quest card.
+fn check_image_sizes(cards_dir: &Path, set: &octgn::Set, definition: &octgn::GameDefinition) {
+    for card in &set.cards {
+        // undeclared sizes are already reported by `GameDefinition::validate`
+        let size = match definition.size(&card.size) {
+            Some(size) if size.width > 0 && size.height > 0 => size,
+            _ => continue,
+        };
+        for file_name in &[format!("{}.jpg", card.id), format!("{}.B.jpg", card.id)] {
+            let dimensions = std::fs::read(cards_dir.join(file_name))
+                .ok()
+                .and_then(|image| image_dimensions(&image));
+            let (width, height) = match dimensions {
+                Some((width, height)) if width > 0 && height > 0 => (width, height),
+                _ => continue,
+            };
+
+            let expected = f64::from(size.width) / f64::from(size.height);
+            let actual = f64::from(width) / f64::from(height);
+            if (actual / expected - 1.0).abs() > MAX_ASPECT_DIFFERENCE {
+                println!(
+                    "Warning: Image {} of OCTGN Card '{}' is {}x{}, but {} cards are {}x{}.",
+                    file_name, card.name, width, height, size.name, size.width, size.height
+                );
+            }
+        }
+    }
+}
+
+/// Download the front and back images of every card, carrying on past failures. Returns the
+/// images that couldn't be downloaded. Offline, every image has to be cached already.
 fn fetch_images(
     work_dir: &Path,
+    game: &octgn::Game,
     set_id: &str,
     cards: &[CardDownload],
-) -> Result<(), Box<std::error::Error>> {
-    let set_dir = work_dir
-        .join(octgn::LOTR_ID)
-        .join("Sets")
-        .join(set_id)
-        .join("Cards");
-    std::fs::create_dir_all(&set_dir)?;
+    options: &DownloadOptions,
+) -> Result<Vec<FailedDownload>, Error> {
+    if options.offline {
+        let missing = cards
+            .iter()
+            .flat_map(|card| {
+                std::iter::once(&card.front_url)
+                    .chain(card.back_url.as_ref())
+                    .filter(|url| is_remote(url))
+                    .filter(|url| {
+                        let cache = options.cache.as_ref();
+                        cache.and_then(|cache| cache.entry(url)).is_none()
+                    })
+                    .map(move |url| format!("image for {} ({})", card.id, url))
+            })
+            .collect::<Vec<String>>();
+        if !missing.is_empty() {
+            return Err(Error::Offline(NotCachedError::new(missing)));
+        }
+    }
 
-    let pb = ProgressBar::new(cards.len() as u64);
+    let set_dir = cards_dir(work_dir, game, set_id);
+    std::fs::create_dir_all(&set_dir)?;
 
-    cards.par_iter().for_each(|card| {
-        {
-            let mut file_path = set_dir.join(&card.id);
-            file_path.set_extension("jpg");
-            let mut file = File::create(&file_path).expect("can't create file");
-            let mut resp = reqwest::get(&card.front_url).expect("can't process request");
-            std::io::copy(&mut resp, &mut file).expect("can't write download to file");
+    let client = reqwest::Client::builder()
+        .timeout(options.timeout)
+        .build()?;
+    let pool = rayon::ThreadPoolBuilder::new()
+        // rayon reads 0 threads as "pick for me", which would mean no cap at all
+        .num_threads(options.max_concurrent.max(1))
+        .build()
+        .map_err(|err| Error::Packaging(Box::new(err)))?;
+    // cards matched to the same Hall of Beorn card share its images, so each URL is downloaded
+    // once and copied to the rest of its files
+    let mut urls: Vec<&str> = vec![];
+    let mut targets: HashMap<&str, Vec<(PathBuf, &str)>> = HashMap::new();
+    for card in cards {
+        let mut sides = vec![(set_dir.join(format!("{}.jpg", &card.id)), &card.front_url)];
+        if let Some(back_url) = &card.back_url {
+            sides.push((set_dir.join(format!("{}.B.jpg", &card.id)), back_url));
         }
-
-        if card.back_url.is_some() {
-            let back_url = card.back_url.as_ref().unwrap();
-            // set_extension replaces the .B
-            let file_path = set_dir.join(format!("{}.B.jpg", &card.id));
-            let mut file = File::create(&file_path).expect("can't create file");
-            let mut resp = reqwest::get(back_url).expect("can't process request");
-            std::io::copy(&mut resp, &mut file).expect("can't write download to file");
+        for (file_path, url) in sides {
+            targets
+                .entry(url)
+                .or_insert_with(|| {
+                    urls.push(url);
+                    vec![]
+                })
+                .push((file_path, &card.id));
         }
-        pb.inc(1);
+    }
+    let pb = ProgressBar::new(urls.len() as u64);
+
+    let failed = pool.install(|| {
+        urls.par_iter()
+            .flat_map(|url| {
+                let (first_path, copies) = targets[url].split_first().unwrap();
+                let failed = match download(&client, url, &first_path.0, options) {
+                    Ok(()) => copies
+                        .iter()
+                        .filter_map(|(file_path, id)| {
+                            std::fs::copy(&first_path.0, file_path)
+                                .err()
+                                .map(|err| FailedDownload::new(id, url, &Error::from(err)))
+                        })
+                        .collect(),
+                    Err(err) => targets[url]
+                        .iter()
+                        .map(|(_, id)| FailedDownload::new(id, url, &err))
+                        .collect::<Vec<FailedDownload>>(),
+                };
+                pb.inc(1);
+
+                failed
+            })
+            .collect()
     });
+    pb.finish();
+
+    Ok(failed)
+}
+
+/// Copy the images for a set's markers, which live in a `Markers` folder next to its set.xml.
+fn copy_markers(work_dir: &Path, set: &octgn::Set) -> Result<(), Error> {
+    let markers_dir = match set.path.as_ref().and_then(|path| path.parent()) {
+        Some(set_dir) => set_dir.join("Markers"),
+        None => return Ok(()),
+    };
+    if set.markers.is_empty() {
+        return Ok(());
+    }
+
+    let images = if markers_dir.is_dir() {
+        std::fs::read_dir(&markers_dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .collect::<Vec<PathBuf>>()
+    } else {
+        vec![]
+    };
+    let set_markers_dir = work_dir
+        .join(set.game.id())
+        .join("Sets")
+        .join(&set.id)
+        .join("Markers");
+    std::fs::create_dir_all(&set_markers_dir)?;
+
+    for marker in &set.markers {
+        let image = images
+            .iter()
+            .find(|path| path.file_stem().and_then(|stem| stem.to_str()) == Some(&marker.id));
+        match image {
+            Some(path) => {
+                std::fs::copy(path, set_markers_dir.join(path.file_name().unwrap()))?;
+            }
+            None => println!(
+                "Warning: Could not find an image for Marker '{}'.",
+                &marker.name
+            ),
+        }
+    }
 
     Ok(())
 }
 
-fn zip_directory(dir: &str, output: &str) -> Result<(), Box<std::error::Error>> {
+fn zip_directory(dir: &str, output: &str) -> Result<(), Error> {
     let file = File::create(output)?;
     let mut zip = zip::ZipWriter::new(file);
     let options =
@@ -143,84 +558,559 @@ fn zip_directory(dir: &str, output: &str) -> Result<(), Box<std::error::Error>>
     Ok(())
 }
 
-fn guess_hob_card<'a>(
-    hob_cards: &'a [hall_of_beorn::Card],
-    unknown_card_name: &str,
-) -> &'a hall_of_beorn::Card {
-    let title = hob_cards
+fn match_octgn_set<'a>(
+    hob_set_name: &str,
+    octgn_sets: &'a [octgn::Set],
+    overrides: &Overrides,
+) -> Option<&'a octgn::Set> {
+    if let Some(octgn_set_name) = overrides.octgn_set(hob_set_name) {
+        let octgn_set = octgn_sets.iter().find(|set| set.name == octgn_set_name);
+        if octgn_set.is_none() {
+            println!(
+                "Warning: Hall of Beorn Set '{}' is pinned to OCTGN Set '{}', which doesn't exist.",
+                hob_set_name, octgn_set_name
+            );
+        }
+
+        return octgn_set;
+    }
+
+    octgn_sets
         .par_iter()
-        .map(|hob_card| {
+        .map(|octgn_set| {
             (
-                &hob_card.title,
-                strsim::levenshtein(&unknown_card_name, &hob_card.title),
+                octgn_set,
+                matching::title_distance(hob_set_name, &octgn_set.name),
             )
         })
         .min_by_key(|&(_, value)| value)
-        .unwrap()
-        .0;
+        .filter(|&(_, value)| value < MAX_SET_LEVENSHTEIN)
+        .map(|(octgn_set, _)| octgn_set)
+}
+
+/// OCTGN set files list card properties in this order.
+const PROPERTY_ORDER: [&str; 17] = [
+    "Card Number",
+    "Quantity",
+    "Encounter Set",
+    "Type",
+    "Sphere",
+    "Traits",
+    "Keywords",
+    "Cost",
+    "Engagement Cost",
+    "Threat",
+    "Willpower",
+    "Attack",
+    "Defense",
+    "Health",
+    "Quest Points",
+    "Text",
+    "Shadow",
+];
+
+fn join_list(items: &[String]) -> Option<String> {
+    if items.is_empty() {
+        None
+    } else {
+        Some(items.join(" "))
+    }
+}
 
-    hob_cards
+fn hob_side_properties(side: &hall_of_beorn::Side) -> Vec<(&'static str, Option<String>)> {
+    let stat = |get: fn(&hall_of_beorn::Stats) -> &Option<String>| {
+        side.stats.as_ref().and_then(|stats| get(stats).clone())
+    };
+
+    vec![
+        ("Traits", join_list(&side.traits)),
+        ("Keywords", join_list(&side.keywords)),
+        (
+            "Cost",
+            stat(|s| &s.threat_cost).or_else(|| stat(|s| &s.resource_cost)),
+        ),
+        ("Engagement Cost", stat(|s| &s.engagement_cost)),
+        ("Threat", stat(|s| &s.threat)),
+        ("Willpower", stat(|s| &s.willpower)),
+        ("Attack", stat(|s| &s.attack)),
+        ("Defense", stat(|s| &s.defense)),
+        ("Health", stat(|s| &s.hit_points)),
+        ("Quest Points", stat(|s| &s.quest_points)),
+        ("Text", join_list(&side.text)),
+        ("Shadow", side.shadow.clone()),
+    ]
+}
+
+/// Keep the first value found for each property and put them in OCTGN's order.
+fn octgn_properties(properties: Vec<(&str, Option<String>)>) -> octgn::Properties {
+    let raw = PROPERTY_ORDER
         .iter()
-        .find(|ref hob_card| &hob_card.title == title)
-        .unwrap()
+        .filter_map(|name| {
+            properties
+                .iter()
+                .filter(|(property, _)| property == name)
+                .find_map(|(_, value)| value.clone())
+                .map(|value| (name.to_string(), value))
+        })
+        .collect();
+
+    octgn::Properties::from_raw(raw)
 }
 
-pub fn pack(set: &octgn::Set) -> Result<(), Box<std::error::Error>> {
-    println!("{}: {}", set.name, set.id);
-    println!("Fetching data from Hall of Beorn");
-    let hob_cards = hall_of_beorn::Card::fetch_all(&set.name)?;
+/// Hall of Beorn separates the words of a type name with underscores, OCTGN with spaces or,
+/// for ships, a hyphen.
+fn hob_card_type(type_name: &str) -> octgn::CardType {
+    match type_name {
+        "Player_Side_Quest" => octgn::CardType::PlayerSideQuest,
+        "Objective_Ally" => octgn::CardType::ObjectiveAlly,
+        "Objective_Hero" => octgn::CardType::ObjectiveHero,
+        "Objective_Location" => octgn::CardType::ObjectiveLocation,
+        "Ship_Enemy" => octgn::CardType::ShipEnemy,
+        "Ship_Objective" => octgn::CardType::ShipObjective,
+        "Encounter_Side_Quest" => octgn::CardType::EncounterSideQuest,
+        _ => octgn::CardType::from(type_name),
+    }
+}
+
+fn hob_to_octgn_card(hob_card: &hall_of_beorn::Card) -> octgn::Card {
+    let card_type = hob_card_type(&hob_card.card_type);
+    let encounter_set = hob_card
+        .encounter_info
+        .as_ref()
+        .map(|info| info.encounter_set.to_string());
+    // quest stages store the stage number and letter in Cost and Engagement Cost
+    let stage_number = hob_card
+        .encounter_info
+        .as_ref()
+        .and_then(|info| info.stage_number)
+        .map(|number| number.to_string());
+    let stage_letter = hob_card
+        .encounter_info
+        .as_ref()
+        .and_then(|info| info.stage_letter.as_ref())
+        .and_then(|letter| letter.chars().next());
+    let size = if card_type == octgn::CardType::Quest {
+        octgn::CardSize::QuestCard
+    } else if card_type.is_player() {
+        octgn::CardSize::Default
+    } else {
+        octgn::CardSize::EncounterCard
+    };
+
+    let mut front = vec![
+        ("Card Number", Some(hob_card.number.to_string())),
+        ("Quantity", Some(hob_card.quantity.to_string())),
+        ("Encounter Set", encounter_set.clone()),
+        ("Type", Some(card_type.to_string())),
+        (
+            "Sphere",
+            hob_card
+                .sphere
+                .clone()
+                .filter(|sphere| sphere.as_str() != "None"),
+        ),
+        ("Cost", stage_number.clone()),
+        (
+            "Engagement Cost",
+            stage_letter.map(|letter| letter.to_string()),
+        ),
+    ];
+    front.extend(hob_side_properties(&hob_card.front));
+
+    let alternates = hob_card
+        .back
+        .as_ref()
+        .map(|back| {
+            let mut properties = vec![
+                ("Encounter Set", encounter_set.clone()),
+                ("Type", Some(card_type.to_string())),
+                ("Cost", stage_number.clone()),
+                (
+                    "Engagement Cost",
+                    stage_letter
+                        .map(|letter| ((letter as u8) + 1) as char)
+                        .map(|letter| letter.to_string()),
+                ),
+            ];
+            properties.extend(hob_side_properties(back));
+
+            vec![octgn::CardSide {
+                alternate_type: "B".to_string(),
+                name: hob_card.title.to_string(),
+                properties: octgn_properties(properties),
+            }]
+        })
+        .unwrap_or_default();
+
+    octgn::Card {
+        id: Uuid::new_v4().to_string(),
+        name: hob_card.title.to_string(),
+        size,
+        attributes: vec![],
+        properties: octgn_properties(front),
+        alternates,
+    }
+}
+
+/// Build a brand new OCTGN Set for a Hall of Beorn set that isn't in the OCTGN repo yet.
+pub fn hob_to_octgn_set(
+    set_name: &str,
+    game: &octgn::Game,
+    game_version: Option<String>,
+    hob_cards: &[hall_of_beorn::Card],
+) -> octgn::Set {
+    octgn::Set {
+        id: Uuid::new_v4().to_string(),
+        name: set_name.to_string(),
+        cards: hob_cards.iter().map(hob_to_octgn_card).collect(),
+        packs: vec![],
+        markers: vec![],
+        game: game.clone(),
+        game_version,
+        version: Some("1.0.0".to_string()),
+        attributes: vec![(
+            "xmlns:noNamespaceSchemaLocation".to_string(),
+            "CardSet.xsd".to_string(),
+        )],
+        path: None,
+    }
+}
+
+fn pack_images(
+    set: &octgn::Set,
+    definition: &octgn::GameDefinition,
+    hob_cards: &[hall_of_beorn::Card],
+    match_options: &MatchOptions,
+    options: &DownloadOptions,
+) -> Result<(), Error> {
     println!("Generating image urls");
-    let card_downloads = get_image_urls(&set.cards, &hob_cards);
+    let card_downloads = get_image_urls(&set.name, &set.cards, hob_cards, match_options)?;
 
     let tmp_dir = TempDir::new("lotr")?;
 
     println!("Downloading images");
-    fetch_images(&tmp_dir.path(), &set.id, &card_downloads)?;
+    let failed = fetch_images(
+        &tmp_dir.path(),
+        &set.game,
+        &set.id,
+        &card_downloads,
+        options,
+    )?;
+    check_image_sizes(
+        &cards_dir(tmp_dir.path(), &set.game, &set.id),
+        set,
+        definition,
+    );
+    copy_markers(tmp_dir.path(), set)?;
     let zip_file = format!("{}.o8c", set.name).replace(" ", "-");
     println!("Zipping file {}", &zip_file);
     zip_directory(&tmp_dir.path().to_str().unwrap(), &zip_file)?;
 
-    Ok(())
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        println!("Could not download {} images:", failed.len());
+        for download in &failed {
+            println!("  {}: {} ({})", download.id, download.url, download.reason);
+        }
+
+        Err(Error::Packaging(Box::new(DownloadsFailedError::new(
+            failed,
+        ))))
+    }
+}
+
+/// The cards of the Hall of Beorn set an OCTGN set was paired with, so `pack` and `match_report`
+/// always match against the same cards.
+fn paired_hob_cards(
+    hob_set: &hall_of_beorn::CardSet,
+    hob_cache: Option<&hall_of_beorn::Cache>,
+) -> Result<Vec<hall_of_beorn::Card>, Error> {
+    hall_of_beorn::Card::fetch_all(&hob_set.name, hob_cache)
+}
+
+/// Write an image pack for `set`, with images from the Hall of Beorn set it was paired with.
+pub fn pack(
+    set: &octgn::Set,
+    definition: &octgn::GameDefinition,
+    hob_set: &hall_of_beorn::CardSet,
+    hob_cache: Option<&hall_of_beorn::Cache>,
+    match_options: &MatchOptions,
+    options: &DownloadOptions,
+) -> Result<(), Error> {
+    println!("{}: {}", set.name, set.id);
+    println!("Fetching data from Hall of Beorn");
+    let hob_cards = paired_hob_cards(hob_set, hob_cache)?;
+    pack_images(set, definition, &hob_cards, match_options, options)
+}
+
+/// Write a set.xml and image pack for a Hall of Beorn set that OCTGN doesn't have yet.
+pub fn generate(
+    hob_set: &hall_of_beorn::CardSet,
+    definition: &octgn::GameDefinition,
+    game_version: Option<String>,
+    hob_cache: Option<&hall_of_beorn::Cache>,
+    match_options: &MatchOptions,
+    options: &DownloadOptions,
+) -> Result<octgn::Set, Error> {
+    println!("Fetching data from Hall of Beorn");
+    let hob_cards = hall_of_beorn::Card::fetch_all(&hob_set.name, hob_cache)?;
+    let set = hob_to_octgn_set(&hob_set.name, &definition.game(), game_version, &hob_cards);
+    println!("{}: {}", set.name, set.id);
+
+    let xml_file = format!("{}.xml", set.name).replace(" ", "-");
+    println!("Writing set file {}", &xml_file);
+    set.write(Path::new(&xml_file))?;
+    pack_images(&set, definition, &hob_cards, match_options, options)?;
+
+    Ok(set)
 }
 
-pub fn sets(dir: &Path) -> Result<Vec<octgn::Set>, Box<std::error::Error>> {
-    let octgn_sets = octgn::Set::fetch_all(&dir)?;
-    let hob_sets = hall_of_beorn::CardSet::fetch_all()?;
+/// OCTGN sets that have a matching Hall of Beorn set, in Hall of Beorn order, along with any set
+/// files that couldn't be loaded.
+pub fn sets(
+    dir: &Path,
+    game: &octgn::Game,
+    hob_cache: Option<&hall_of_beorn::Cache>,
+    overrides: &Overrides,
+) -> Result<(Vec<octgn::Set>, Vec<octgn::SetLoadError>), Error> {
+    let (pairs, errors) = paired_sets(dir, game, hob_cache, overrides)?;
+    let sets = pairs.into_iter().map(|(set, _)| set).collect();
+
+    Ok((sets, errors))
+}
+
+/// An OCTGN set and the Hall of Beorn set it was matched to.
+pub type SetPair = (octgn::Set, hall_of_beorn::CardSet);
+
+/// Like `sets`, but with the Hall of Beorn set each OCTGN set was matched to.
+pub fn paired_sets(
+    dir: &Path,
+    game: &octgn::Game,
+    hob_cache: Option<&hall_of_beorn::Cache>,
+    overrides: &Overrides,
+) -> Result<(Vec<SetPair>, Vec<octgn::SetLoadError>), Error> {
+    let (octgn_sets, errors) = octgn::Set::fetch_all_tolerant(&dir, game);
+    let hob_sets = hall_of_beorn::CardSet::fetch_all(hob_cache)?;
 
     // only care about octgn sets that also have a matching hob set
-    let ordered_set_names: Vec<String> = hob_sets
+    let matches: Vec<(usize, hall_of_beorn::CardSet)> = hob_sets
         .into_par_iter()
         .filter_map(|hob_set| {
-            let min_match_set = octgn_sets
-                .par_iter()
-                .map(|octgn_set| {
-                    (
-                        &octgn_set.name,
-                        strsim::levenshtein(&hob_set.name, &octgn_set.name),
-                    )
-                })
-                .min_by_key(|&(_, value)| value)
-                .unwrap();
+            let octgn_set = match_octgn_set(&hob_set.name, &octgn_sets, overrides)?;
+            let index = octgn_sets
+                .iter()
+                .position(|set| std::ptr::eq(set, octgn_set))?;
 
-            if min_match_set.1 < MAX_SET_LEVENSHTEIN {
-                Some(min_match_set.0.clone())
-            } else {
-                None
-            }
+            Some((index, hob_set))
         })
         .collect();
 
-    let mut ordered_sets = octgn_sets
-        .into_par_iter()
-        .filter(|set| ordered_set_names.contains(&set.name))
-        .collect::<Vec<octgn::Set>>();
-    ordered_sets.sort_by_key(|set| {
-        ordered_set_names
-            .par_iter()
-            .position_any(|name| &set.name == name)
-    });
+    // an octgn set goes with the first hob set that matched it
+    let mut octgn_sets = octgn_sets.into_iter().map(Some).collect::<Vec<_>>();
+    let pairs = matches
+        .into_iter()
+        .filter_map(|(index, hob_set)| octgn_sets[index].take().map(|set| (set, hob_set)))
+        .collect();
+
+    Ok((pairs, errors))
+}
+
+/// How the cards in every set `sets` lists would be matched, without downloading any images.
+pub fn match_report(
+    dir: &Path,
+    game: &octgn::Game,
+    hob_cache: Option<&hall_of_beorn::Cache>,
+    match_options: &MatchOptions,
+) -> Result<(MatchReport, Vec<octgn::SetLoadError>), Error> {
+    let (pairs, errors) = paired_sets(dir, game, hob_cache, &match_options.overrides)?;
+    let mut report = MatchReport::default();
+    for (octgn_set, hob_set) in &pairs {
+        let hob_cards = paired_hob_cards(hob_set, hob_cache)?;
+        report.add_set(&octgn_set.name, &octgn_set.cards, &hob_cards, match_options);
+    }
+
+    Ok((report, errors))
+}
+
+/// Which sets to pack.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SetFilter {
+    All,
+    /// OCTGN set ids.
+    Ids(Vec<String>),
+    /// A Hall of Beorn cycle, like "Dwarrowdelf".
+    Cycle(String),
+    /// A Hall of Beorn set type, like "Deluxe_Expansion".
+    SetType(String),
+}
+
+impl SetFilter {
+    pub fn matches(&self, set: &octgn::Set, hob_set: &hall_of_beorn::CardSet) -> bool {
+        match self {
+            SetFilter::All => true,
+            SetFilter::Ids(ids) => ids.contains(&set.id),
+            SetFilter::Cycle(cycle) => hob_set
+                .cycle
+                .as_ref()
+                .is_some_and(|hob_cycle| hob_cycle.eq_ignore_ascii_case(cycle)),
+            SetFilter::SetType(set_type) => hob_set.set_type.eq_ignore_ascii_case(set_type),
+        }
+    }
+}
+
+/// The OCTGN card with the title closest to `name`, unless even that one is too far off to trust.
+fn guess_octgn_card<'a>(octgn_cards: &'a [octgn::Card], name: &str) -> Option<&'a octgn::Card> {
+    let options = MatchOptions::default();
+
+    octgn_cards
+        .iter()
+        .map(|octgn_card| (octgn_card, matching::title_distance(name, &octgn_card.name)))
+        .min_by_key(|&(_, distance)| distance)
+        .filter(|&(octgn_card, distance)| options.accepts(name, &octgn_card.name, distance))
+        .map(|(octgn_card, _)| octgn_card)
+}
+
+fn ringsdb_to_octgn_card<'a>(
+    entry: &ringsdb::DeckEntry,
+    octgn_sets: &'a [octgn::Set],
+) -> Option<&'a octgn::Card> {
+    let set = match match_octgn_set(&entry.pack_name, octgn_sets, &Overrides::default()) {
+        Some(set) => set,
+        None => {
+            return octgn_sets
+                .iter()
+                .flat_map(|set| set.cards.iter())
+                .find(|card| card.name == entry.name)
+        }
+    };
+
+    // RingsDB and OCTGN don't always number a set the same way, so the title has to agree too
+    let options = MatchOptions::default();
+    entry
+        .number
+        .and_then(|number| {
+            set.cards
+                .iter()
+                .find(|card| card.properties.number == Some(number))
+        })
+        .filter(|card| {
+            let distance = matching::title_distance(&entry.name, &card.name);
+            options.accepts(&entry.name, &card.name, distance)
+        })
+        .or_else(|| set.cards.iter().find(|card| card.name == entry.name))
+        .or_else(|| {
+            let card = guess_octgn_card(&set.cards, &entry.name)?;
+            println!(
+                "Warning: Could not find RingsDB Card '{}', using OCTGN Card '{}' instead.",
+                &entry.name, card.name
+            );
+
+            Some(card)
+        })
+}
+
+/// Build an OCTGN deck from RingsDB deck entries, along with descriptions of the entries that
+/// couldn't be matched to an OCTGN card.
+pub fn ringsdb_to_octgn_deck(
+    entries: &[ringsdb::DeckEntry],
+    octgn_sets: &[octgn::Set],
+    game: &octgn::Game,
+) -> Result<(octgn::Deck, Vec<String>), Error> {
+    let mut unmatched = vec![];
+    let mut cards = vec![];
+    let mut sideboard = vec![];
+
+    for entry in entries {
+        match ringsdb_to_octgn_card(entry, octgn_sets) {
+            Some(card) if entry.sideboard => sideboard.push(octgn::DeckCard {
+                id: card.id.to_string(),
+                name: card.name.to_string(),
+                quantity: entry.quantity,
+            }),
+            Some(card) => cards.push((card.id.to_string(), entry.quantity)),
+            None => unmatched.push(format!(
+                "{}x {} ({})",
+                entry.quantity, entry.name, entry.pack_name
+            )),
+        }
+    }
+
+    let mut deck = octgn::Deck::from_cards(&cards, octgn_sets, game)?;
+    if let Some(section) = deck
+        .sections
+        .iter_mut()
+        .find(|section| section.name == "Sideboard")
+    {
+        section.cards.extend(sideboard);
+    }
+
+    Ok((deck, unmatched))
+}
+
+/// Convert a RingsDB deck export, either the JSON or the plain text format, into an OCTGN deck.
+pub fn import_ringsdb(
+    path: &Path,
+    octgn_sets: &[octgn::Set],
+    game: &octgn::Game,
+) -> Result<(octgn::Deck, Vec<String>), Error> {
+    let mut file = File::open(path)?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+
+    let (entries, unknown_codes) = if content.trim_start().starts_with('{') {
+        let ringsdb_deck = serde_json::from_str::<ringsdb::Deck>(&content).map_err(Error::Json)?;
+        println!("Fetching data from RingsDB");
+        let ringsdb_cards = ringsdb::Card::fetch_all()?;
+        ringsdb_deck.entries(&ringsdb_cards)
+    } else {
+        let entries = ringsdb::parse_text(&content);
+        if entries.is_empty() {
+            return Err(Error::Json(serde::de::Error::custom(
+                "no cards found in the text export",
+            )));
+        }
+        (entries, vec![])
+    };
+
+    let (deck, mut unmatched) = ringsdb_to_octgn_deck(&entries, octgn_sets, game)?;
+    unmatched.extend(
+        unknown_codes
+            .into_iter()
+            .map(|code| format!("RingsDB card code {}", code)),
+    );
+
+    Ok((deck, unmatched))
+}
+
+/// Hall of Beorn sets without a matching OCTGN set, the OCTGN game version to use when generating
+/// them, and any set files that couldn't be loaded.
+pub type MissingSets = (
+    Vec<hall_of_beorn::CardSet>,
+    Option<String>,
+    Vec<octgn::SetLoadError>,
+);
+
+/// Find the Hall of Beorn sets OCTGN doesn't have yet, see `MissingSets`.
+pub fn missing_sets(
+    dir: &Path,
+    game: &octgn::Game,
+    hob_cache: Option<&hall_of_beorn::Cache>,
+    overrides: &Overrides,
+) -> Result<MissingSets, Error> {
+    let (octgn_sets, errors) = octgn::Set::fetch_all_tolerant(dir, game);
+    let hob_sets = hall_of_beorn::CardSet::fetch_all(hob_cache)?;
+    let game_version = octgn_sets
+        .iter()
+        .filter_map(|set| set.game_version.clone())
+        .max();
 
-    Ok(ordered_sets)
+    let missing = hob_sets
+        .into_iter()
+        .filter(|hob_set| match_octgn_set(&hob_set.name, &octgn_sets, overrides).is_none())
+        .collect();
+
+    Ok((missing, game_version, errors))
 }
 
 #[cfg(test)]
@@ -228,28 +1118,59 @@ mod tests {
     pub mod mocks;
 
     use self::mocks::hall_of_beorn as hob_mocks;
+    use self::mocks::ringsdb as ringsdb_mocks;
     use super::*;
 
     use mockito::mock;
 
-    fn load_hall_of_beorn() -> Vec<hall_of_beorn::Card> {
-        let set = "The Wilds of Rhovanion";
-        let _m = hob_mocks::card_set(&set).unwrap();
+    pub const SET: &str = "The Wilds of Rhovanion";
+
+    /// The parse error inside an `Error::Xml`, the way `Box::downcast` would give it.
+    pub fn downcast_xml<T: std::error::Error + 'static>(
+        err: Error,
+    ) -> Result<Box<T>, Box<dyn std::error::Error>> {
+        match err {
+            Error::Xml(err) => err.downcast::<T>(),
+            err => Err(Box::new(err)),
+        }
+    }
 
-        hall_of_beorn::Card::fetch_all(set).unwrap()
+    /// The Hall of Beorn cards in `SET`, served by the mock.
+    pub fn load_hall_of_beorn() -> Vec<hall_of_beorn::Card> {
+        let _m = hob_mocks::card_set(SET).unwrap();
+
+        hall_of_beorn::Card::fetch_all(SET, None).unwrap()
+    }
+
+    /// The OCTGN set in `fixtures/set.xml`, `SET`.
+    pub fn load_octgn_set() -> octgn::Set {
+        let xml = std::fs::read_to_string("fixtures/set.xml").unwrap();
+        let doc = roxmltree::Document::parse(&xml).unwrap();
+
+        octgn::Set::new(&doc, &octgn::Game::LOTR).unwrap()
+    }
+
+    /// A player card with nothing but an id and a name.
+    pub fn octgn_card(id: &str, name: &str) -> octgn::Card {
+        octgn::Card {
+            id: id.to_string(),
+            name: name.to_string(),
+            size: octgn::CardSize::Default,
+            attributes: vec![],
+            properties: octgn::Properties::default(),
+            alternates: vec![],
+        }
     }
 
     #[test]
     fn test_get_image_urls_normal_card() {
         let hob_cards = load_hall_of_beorn();
-        let brand_son_of_bain = octgn::Card {
-            id: "2b75792d-5873-4fc6-9272-d20dd517d36b".to_string(),
-            name: "Brand son of Bain".to_string(),
-            back_name: None,
-        };
+        let brand_son_of_bain =
+            octgn_card("2b75792d-5873-4fc6-9272-d20dd517d36b", "Brand son of Bain");
         let octgn_cards = vec![brand_son_of_bain];
 
-        let card_downloads = get_image_urls(&octgn_cards, &hob_cards);
+        let card_downloads =
+            get_image_urls(SET, &octgn_cards, &hob_cards, &MatchOptions::default()).unwrap();
         assert_eq!(card_downloads.len(), 1);
 
         let card = card_downloads.get(0).unwrap();
@@ -261,14 +1182,11 @@ mod tests {
     #[test]
     fn test_get_image_urls_unknown_card() {
         let hob_cards = load_hall_of_beorn();
-        let fire_drake = octgn::Card {
-            id: "42a5a608-0699-4cd5-b69d-f7c3413cd5cd".to_string(),
-            name: "Fire Drake".to_string(),
-            back_name: None,
-        };
+        let fire_drake = octgn_card("42a5a608-0699-4cd5-b69d-f7c3413cd5cd", "Fire Drake");
         let octgn_cards = vec![fire_drake];
 
-        let card_downloads = get_image_urls(&octgn_cards, &hob_cards);
+        let card_downloads =
+            get_image_urls(SET, &octgn_cards, &hob_cards, &MatchOptions::default()).unwrap();
         assert_eq!(card_downloads.len(), 1);
 
         let card = card_downloads.get(0).unwrap();
@@ -281,13 +1199,17 @@ mod tests {
     fn test_get_image_urls_quest_card() {
         let hob_cards = load_hall_of_beorn();
         let traveling_north = octgn::Card {
-            id: "b4b0bdc8-5edb-40c9-a9ca-dac60fe7cb38".to_string(),
-            name: "Traveling North".to_string(),
-            back_name: Some("Traveling North".to_string()),
+            alternates: vec![octgn::CardSide {
+                alternate_type: "B".to_string(),
+                name: "Traveling North".to_string(),
+                properties: octgn::Properties::default(),
+            }],
+            ..octgn_card("b4b0bdc8-5edb-40c9-a9ca-dac60fe7cb38", "Traveling North")
         };
         let octgn_cards = vec![traveling_north];
 
-        let card_downloads = get_image_urls(&octgn_cards, &hob_cards);
+        let card_downloads =
+            get_image_urls(SET, &octgn_cards, &hob_cards, &MatchOptions::default()).unwrap();
         assert_eq!(card_downloads.len(), 1);
 
         let card = card_downloads.get(0).unwrap();
@@ -303,13 +1225,24 @@ mod tests {
     fn test_get_image_urls_back_name_card() {
         let hob_cards = load_hall_of_beorn();
         let woodman_village = octgn::Card {
-            id: "1d4d59f4-def5-4c9e-ba3f-8a28e7f66c73".to_string(),
-            name: "Woodman Village".to_string(),
-            back_name: Some("Haldan".to_string()),
+            properties: octgn::Properties::from_raw(vec![(
+                "Type".to_string(),
+                "Location".to_string(),
+            )]),
+            alternates: vec![octgn::CardSide {
+                alternate_type: "B".to_string(),
+                name: "Haldan".to_string(),
+                properties: octgn::Properties::from_raw(vec![(
+                    "Type".to_string(),
+                    "Objective Ally".to_string(),
+                )]),
+            }],
+            ..octgn_card("1d4d59f4-def5-4c9e-ba3f-8a28e7f66c73", "Woodman Village")
         };
         let octgn_cards = vec![woodman_village];
 
-        let card_downloads = get_image_urls(&octgn_cards, &hob_cards);
+        let card_downloads =
+            get_image_urls(SET, &octgn_cards, &hob_cards, &MatchOptions::default()).unwrap();
         assert_eq!(card_downloads.len(), 1);
 
         let card = card_downloads.get(0).unwrap();
@@ -321,6 +1254,33 @@ mod tests {
         assert_eq!(result, "https://s3.amazonaws.com/hallofbeorn-resources/Images/Cards/The-Wilds-of-Rhovanion/Haldan.jpg");
     }
 
+    #[test]
+    fn test_get_image_urls_unmatched_card() {
+        let hob_cards = load_hall_of_beorn();
+        let gandalf = octgn_card("9fc0bc24-7f02-4e27-9c4c-a4a9e4e38b88", "Gandalf");
+        let octgn_cards = vec![gandalf];
+
+        let card_downloads =
+            get_image_urls(SET, &octgn_cards, &hob_cards, &MatchOptions::default()).unwrap();
+        assert!(card_downloads.is_empty());
+
+        let placeholder = MatchOptions {
+            unmatched: UnmatchedAction::Placeholder(PathBuf::from("placeholder.jpg")),
+            ..MatchOptions::default()
+        };
+        let card_downloads = get_image_urls(SET, &octgn_cards, &hob_cards, &placeholder).unwrap();
+        assert_eq!(&card_downloads[0].front_url, "placeholder.jpg");
+
+        let strict = MatchOptions {
+            unmatched: UnmatchedAction::Fail,
+            ..MatchOptions::default()
+        };
+        match get_image_urls(SET, &octgn_cards, &hob_cards, &strict) {
+            Err(err @ Error::Matching(_)) => assert_eq!(err.exit_code(), 8),
+            _ => panic!("expected Gandalf to be unmatched"),
+        }
+    }
+
     #[test]
     fn test_fetch_images_normal() {
         let tmp_dir = TempDir::new("lotr-test").unwrap();
@@ -338,8 +1298,14 @@ mod tests {
 
         let cards = vec![brand_son_of_bain];
 
-        let result = fetch_images(&tmp_dir.path(), set_id, &cards);
-        assert!(result.is_ok());
+        let result = fetch_images(
+            &tmp_dir.path(),
+            &octgn::Game::LOTR,
+            set_id,
+            &cards,
+            &DownloadOptions::default(),
+        );
+        assert!(result.unwrap().is_empty());
 
         let image_path = &tmp_dir
             .path()
@@ -354,6 +1320,49 @@ mod tests {
         assert_eq!(card_id, content);
     }
 
+    #[test]
+    fn test_fetch_images_shared_url() {
+        let tmp_dir = TempDir::new("lotr-test").unwrap();
+        let set_id = "e37145f0-8970-48d3-93bc-cef612226bda";
+        let url = format!("{}/Images/Cards/Fire-drake.jpg", mockito::SERVER_URL);
+        // two OCTGN cards matched to the same Hall of Beorn card
+        let card_ids = vec![
+            "42a5a608-0699-4cd5-b69d-f7c3413cd5cd",
+            "a8b1e0b4-4b9e-4a2b-9a0a-3f8f1e2d6c11",
+        ];
+        let cards = card_ids
+            .iter()
+            .map(|card_id| CardDownload {
+                id: card_id.to_string(),
+                front_url: url.to_string(),
+                back_url: None,
+            })
+            .collect::<Vec<CardDownload>>();
+        let m = mock("GET", "/Images/Cards/Fire-drake.jpg")
+            .with_header("content-type", "image/jpeg")
+            .with_body("Fire-drake")
+            .expect(1)
+            .create();
+
+        let failed = fetch_images(
+            tmp_dir.path(),
+            &octgn::Game::LOTR,
+            set_id,
+            &cards,
+            &DownloadOptions::default(),
+        )
+        .unwrap();
+        m.assert();
+        assert!(failed.is_empty());
+        let cards_dir = cards_dir(tmp_dir.path(), &octgn::Game::LOTR, set_id);
+        for card_id in card_ids {
+            assert_eq!(
+                std::fs::read_to_string(cards_dir.join(format!("{}.jpg", card_id))).unwrap(),
+                "Fire-drake"
+            );
+        }
+    }
+
     #[test]
     fn test_fetch_images_back() {
         let tmp_dir = TempDir::new("lotr-test").unwrap();
@@ -375,8 +1384,14 @@ mod tests {
 
         let cards = vec![woodman_village];
 
-        let result = fetch_images(&tmp_dir.path(), set_id, &cards);
-        assert!(result.is_ok());
+        let result = fetch_images(
+            &tmp_dir.path(),
+            &octgn::Game::LOTR,
+            set_id,
+            &cards,
+            &DownloadOptions::default(),
+        );
+        assert!(result.unwrap().is_empty());
 
         let image_path = &tmp_dir
             .path()
@@ -404,20 +1419,599 @@ mod tests {
     }
 
     #[test]
-    fn test_guess_hob_card() {
+    fn test_fetch_images_failed() {
+        let tmp_dir = TempDir::new("lotr-test").unwrap();
+        let set_id = "e37145f0-8970-48d3-93bc-cef612226bda";
+        let card_id = "1d4d59f4-def5-4c9e-ba3f-8a28e7f66c73";
+        let woodman_village = CardDownload {
+            id: card_id.to_string(),
+            front_url: format!("{}/Images/Cards/Woodmen-village.jpg", mockito::SERVER_URL),
+            back_url: Some(format!("{}/Images/Cards/Missing.jpg", mockito::SERVER_URL)),
+        };
+        let _m = mock("GET", "/Images/Cards/Woodmen-village.jpg")
+            .with_header("content-type", "image/jpeg")
+            .with_body("Woodmen Village")
+            .create();
+        let _m2 = mock("GET", "/Images/Cards/Missing.jpg")
+            .with_status(404)
+            .with_body("Not Found")
+            .create();
+
+        let cards = vec![woodman_village];
+
+        let failed = fetch_images(
+            tmp_dir.path(),
+            &octgn::Game::LOTR,
+            set_id,
+            &cards,
+            &DownloadOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(&failed[0].id, card_id);
+        assert_eq!(
+            failed[0].url,
+            format!("{}/Images/Cards/Missing.jpg", mockito::SERVER_URL)
+        );
+        assert!(failed[0].reason.starts_with("HTTP request failed"));
+
+        let cards_dir = tmp_dir
+            .path()
+            .join(octgn::LOTR_ID)
+            .join("Sets")
+            .join(set_id)
+            .join("Cards");
+        assert!(cards_dir.join(format!("{}.jpg", card_id)).exists());
+        // the 404 page isn't saved as an image
+        assert!(!cards_dir.join(format!("{}.B.jpg", card_id)).exists());
+
+        let err = DownloadsFailedError::new(failed);
+        assert_eq!(
+            err.to_string(),
+            format!("Could not download 1 images for cards: {}", card_id)
+        );
+    }
+
+    #[test]
+    fn test_fetch_images_retry() {
+        let tmp_dir = TempDir::new("lotr-test").unwrap();
+        let set_id = "e37145f0-8970-48d3-93bc-cef612226bda";
+        let card_id = "b69ae054-e422-43c7-a9b3-42f334ab61a5";
+        let (url, server) = mocks::flaky::server(2, "Redwater Sentry");
+        let redwater_sentry = CardDownload {
+            id: card_id.to_string(),
+            front_url: format!("{}/Images/Cards/Redwater-Sentry.jpg", url),
+            back_url: None,
+        };
+        let options = DownloadOptions {
+            backoff: Duration::from_millis(0),
+            ..DownloadOptions::default()
+        };
+
+        let failed = fetch_images(
+            tmp_dir.path(),
+            &octgn::Game::LOTR,
+            set_id,
+            &[redwater_sentry],
+            &options,
+        )
+        .unwrap();
+        assert!(failed.is_empty());
+        // two 503s, then the image
+        assert_eq!(server.join().unwrap(), 3);
+        let image_path = tmp_dir
+            .path()
+            .join(octgn::LOTR_ID)
+            .join("Sets")
+            .join(set_id)
+            .join("Cards")
+            .join(format!("{}.jpg", card_id));
+        assert_eq!(
+            std::fs::read_to_string(image_path).unwrap(),
+            "Redwater Sentry"
+        );
+    }
+
+    #[test]
+    fn test_fetch_images_retries_exhausted() {
+        let tmp_dir = TempDir::new("lotr-test").unwrap();
+        let set_id = "e37145f0-8970-48d3-93bc-cef612226bda";
+        let card_id = "552b11aa-b91e-44aa-8e54-1bad1aa74bbd";
+        let stray_goblin = CardDownload {
+            id: card_id.to_string(),
+            front_url: format!("{}/Images/Cards/Stray-Goblin.jpg", mockito::SERVER_URL),
+            back_url: None,
+        };
+        let options = DownloadOptions {
+            retries: 2,
+            backoff: Duration::from_millis(0),
+            ..DownloadOptions::default()
+        };
+        let m = mock("GET", "/Images/Cards/Stray-Goblin.jpg")
+            .with_status(503)
+            .expect(3)
+            .create();
+
+        let failed = fetch_images(
+            tmp_dir.path(),
+            &octgn::Game::LOTR,
+            set_id,
+            &[stray_goblin],
+            &options,
+        )
+        .unwrap();
+        m.assert();
+        assert_eq!(failed.len(), 1);
+        assert!(failed[0].reason.contains("503"));
+    }
+
+    #[test]
+    fn test_image_dimensions() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend(&[0, 0, 0x01, 0xAD, 0, 0, 0x02, 0x58]);
+        assert_eq!(image_dimensions(&png), Some((429, 600)));
+
+        let jpeg = [
+            0xFF, 0xD8, // start of image
+            0xFF, 0xE0, 0x00, 0x04, 0x4A, 0x46, // APP0
+            0xFF, 0xC4, 0x00, 0x02, // huffman table
+            0xFF, 0xC2, 0x00, 0x0B, 0x08, 0x01, 0xAD, 0x02, 0x58, // progressive frame
+        ];
+        assert_eq!(image_dimensions(&jpeg), Some((600, 429)));
+
+        assert_eq!(image_dimensions(b"Redwater Sentry"), None);
+        assert_eq!(image_dimensions(&jpeg[..12]), None);
+    }
+
+    #[test]
+    fn test_is_transient() {
+        let client = reqwest::Client::new();
+        let refused = client.get("http://127.0.0.1:1/").send().unwrap_err();
+        assert!(is_transient(&refused));
+
+        let bad_header = client
+            .get(mockito::SERVER_URL)
+            .header("If-None-Match", "\"Redwater\nSentry\"")
+            .send()
+            .unwrap_err();
+        assert!(!is_transient(&bad_header));
+    }
+
+    #[test]
+    fn test_fetch_images_cached() {
+        let tmp_dir = TempDir::new("lotr-test").unwrap();
+        let set_id = "e37145f0-8970-48d3-93bc-cef612226bda";
+        let card_id = "7d5f1011-3c36-4454-9efb-bf09553011d9";
+        let url = format!("{}/Images/Cards/Bard-son-of-Brand.jpg", mockito::SERVER_URL);
+        let bard_son_of_brand = CardDownload {
+            id: card_id.to_string(),
+            front_url: url.to_string(),
+            back_url: None,
+        };
+        let cache = ImageCache::new(tmp_dir.path().join("images"));
+        let options = DownloadOptions {
+            cache: Some(cache.clone()),
+            ..DownloadOptions::default()
+        };
+        let cards = vec![bard_son_of_brand];
+        let image_path = tmp_dir
+            .path()
+            .join("pack")
+            .join(octgn::LOTR_ID)
+            .join("Sets")
+            .join(set_id)
+            .join("Cards")
+            .join(format!("{}.jpg", card_id));
+
+        let _m = mock("GET", "/Images/Cards/Bard-son-of-Brand.jpg")
+            .with_header("content-type", "image/jpeg")
+            .with_header("etag", "\"bard-1\"")
+            .with_body("Bard son of Brand")
+            .create();
+        let failed = fetch_images(
+            &tmp_dir.path().join("pack"),
+            &octgn::Game::LOTR,
+            set_id,
+            &cards,
+            &options,
+        )
+        .unwrap();
+        assert!(failed.is_empty());
+        assert_eq!(
+            cache.entry(&url).unwrap().etag.as_ref().unwrap(),
+            "\"bard-1\""
+        );
+
+        // a second pack only revalidates the image
+        std::fs::remove_dir_all(tmp_dir.path().join("pack")).unwrap();
+        let not_modified = mock("GET", "/Images/Cards/Bard-son-of-Brand.jpg")
+            .match_header("if-none-match", "\"bard-1\"")
+            .with_status(304)
+            .create();
+        let failed = fetch_images(
+            &tmp_dir.path().join("pack"),
+            &octgn::Game::LOTR,
+            set_id,
+            &cards,
+            &options,
+        )
+        .unwrap();
+        assert!(failed.is_empty());
+        not_modified.assert();
+        assert_eq!(
+            std::fs::read_to_string(&image_path).unwrap(),
+            "Bard son of Brand"
+        );
+    }
+
+    #[test]
+    fn test_fetch_images_offline() {
+        let tmp_dir = TempDir::new("lotr-test").unwrap();
+        let set_id = "e37145f0-8970-48d3-93bc-cef612226bda";
+        let card_id = "7d5f1011-3c36-4454-9efb-bf09553011d9";
+        let url = format!("{}/Images/Cards/Bard-son-of-Brand.jpg", mockito::SERVER_URL);
+        let back_url = format!(
+            "{}/Images/Cards/Bard-son-of-Brand-B.jpg",
+            mockito::SERVER_URL
+        );
+        let cards = vec![CardDownload {
+            id: card_id.to_string(),
+            front_url: url.to_string(),
+            back_url: Some(back_url.to_string()),
+        }];
+        let cache = ImageCache::new(tmp_dir.path().join("images"));
+        cache
+            .store(&url, &mut "Bard son of Brand".as_bytes(), None, None)
+            .unwrap();
+        let options = DownloadOptions {
+            cache: Some(cache.clone()),
+            offline: true,
+            ..DownloadOptions::default()
+        };
+
+        match fetch_images(
+            &tmp_dir.path().join("pack"),
+            &octgn::Game::LOTR,
+            set_id,
+            &cards,
+            &options,
+        ) {
+            Err(Error::Offline(err)) => assert_eq!(
+                err.missing,
+                vec![format!("image for {} ({})", card_id, back_url)]
+            ),
+            _ => panic!("expected the back image to be missing from the cache"),
+        }
+
+        cache
+            .store(&back_url, &mut "Back".as_bytes(), None, None)
+            .unwrap();
+        let failed = fetch_images(
+            &tmp_dir.path().join("pack"),
+            &octgn::Game::LOTR,
+            set_id,
+            &cards,
+            &options,
+        )
+        .unwrap();
+        assert!(failed.is_empty());
+        let image_path = tmp_dir
+            .path()
+            .join("pack")
+            .join(octgn::LOTR_ID)
+            .join("Sets")
+            .join(set_id)
+            .join("Cards")
+            .join(format!("{}.B.jpg", card_id));
+        assert_eq!(std::fs::read_to_string(&image_path).unwrap(), "Back");
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let options = DownloadOptions {
+            backoff: Duration::from_millis(100),
+            ..DownloadOptions::default()
+        };
+
+        for attempt in 0..3 {
+            let delay = options.backoff_delay(attempt);
+            let base = Duration::from_millis(100 * 2u64.pow(attempt));
+            assert!(delay >= base);
+            assert!(delay <= base * 2);
+        }
+    }
+
+    #[test]
+    fn test_copy_markers() {
+        let tmp_dir = TempDir::new("lotr-test").unwrap();
+        let set_dir = tmp_dir.path().join("octgn").join("The Wilds of Rhovanion");
+        let markers_dir = set_dir.join("Markers");
+        std::fs::create_dir_all(&markers_dir).unwrap();
+        let marker_id = "2f0a5b1e-9a43-4f7b-8f3a-6a2c1d9e0b7a";
+        let mut file = File::create(markers_dir.join(format!("{}.png", marker_id))).unwrap();
+        file.write_all(b"Resource").unwrap();
+
+        let set = octgn::Set {
+            id: "e37145f0-8970-48d3-93bc-cef612226bda".to_string(),
+            name: "The Wilds of Rhovanion".to_string(),
+            cards: vec![],
+            packs: vec![],
+            markers: vec![octgn::Marker {
+                id: marker_id.to_string(),
+                name: "Resource".to_string(),
+            }],
+            game: octgn::Game::LOTR,
+            game_version: None,
+            version: None,
+            attributes: vec![],
+            path: Some(set_dir.join("set.xml")),
+        };
+        let work_dir = tmp_dir.path().join("work");
+
+        let result = copy_markers(&work_dir, &set);
+        assert!(result.is_ok());
+
+        let image_path = work_dir
+            .join(octgn::LOTR_ID)
+            .join("Sets")
+            .join(&set.id)
+            .join("Markers")
+            .join(format!("{}.png", marker_id));
+        let mut content = String::new();
+        File::open(&image_path)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "Resource");
+    }
+
+    #[test]
+    fn test_hob_to_octgn_set() {
+        let hob_cards = load_hall_of_beorn();
+        let set = hob_to_octgn_set(
+            "The Wilds of Rhovanion",
+            &octgn::Game::LOTR,
+            Some("2.3.6.0".to_string()),
+            &hob_cards,
+        );
+        assert_eq!(&set.name, "The Wilds of Rhovanion");
+        assert_eq!(set.game, octgn::Game::LOTR);
+        assert_eq!(set.cards.len(), 80);
+
+        let brand = &set.cards[0];
+        assert_eq!(&brand.name, "Brand son of Bain");
+        assert!(brand.is_player_card());
+        assert_eq!(
+            brand.properties.raw,
+            vec![
+                ("Card Number".to_string(), "1".to_string()),
+                ("Quantity".to_string(), "1".to_string()),
+                ("Type".to_string(), "Hero".to_string()),
+                ("Sphere".to_string(), "Leadership".to_string()),
+                ("Traits".to_string(), "Dale. Noble.".to_string()),
+                ("Cost".to_string(), "10".to_string()),
+                ("Willpower".to_string(), "2".to_string()),
+                ("Attack".to_string(), "3".to_string()),
+                ("Defense".to_string(), "2".to_string()),
+                ("Health".to_string(), "3".to_string()),
+                ("Text".to_string(), "Each Dale character you control with a player attachment gets +1 Willpower. Response: After you play an attachment on a Dale character without an attachment, draw a card.".to_string()),
+            ]
+        );
+
+        let fire_drake = set
+            .cards
+            .iter()
+            .find(|card| card.name == "Fire-drake")
+            .unwrap();
+        assert!(fire_drake.is_encounter_card());
+        assert_eq!(
+            fire_drake.properties.card_type,
+            Some(octgn::CardType::Enemy)
+        );
+        assert_eq!(
+            fire_drake.properties.encounter_set.as_ref().unwrap(),
+            "The King's Quest"
+        );
+        assert_eq!(fire_drake.properties.engagement_cost, Some(35));
+        assert_eq!(fire_drake.properties.get("Threat"), Some("X"));
+
+        let haldan = set.cards.iter().find(|card| card.name == "Haldan").unwrap();
+        assert_eq!(
+            haldan.properties.card_type,
+            Some(octgn::CardType::ObjectiveAlly)
+        );
+
+        let traveling_north = set
+            .cards
+            .iter()
+            .find(|card| card.name == "Traveling North")
+            .unwrap();
+        assert!(traveling_north.is_quest_card());
+        assert_eq!(traveling_north.properties.cost, Some(1));
+        assert_eq!(traveling_north.properties.get("Engagement Cost"), Some("A"));
+        let back = traveling_north.back().unwrap();
+        assert_eq!(&back.name, "Traveling North");
+        assert_eq!(back.properties.get("Engagement Cost"), Some("B"));
+        assert!(back.properties.text.as_ref().unwrap().starts_with("Setup:"));
+        assert!(traveling_north.is_same_card(back));
+
+        let mut ids = set.cards.iter().map(|card| &card.id).collect::<Vec<_>>();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 80);
+    }
+
+    #[test]
+    fn test_hob_card_type() {
+        assert_eq!(hob_card_type("Hero"), octgn::CardType::Hero);
+        assert_eq!(
+            hob_card_type("Objective_Ally"),
+            octgn::CardType::ObjectiveAlly
+        );
+        assert_eq!(hob_card_type("Ship_Enemy"), octgn::CardType::ShipEnemy);
+        assert_eq!(
+            hob_card_type("Ship_Objective"),
+            octgn::CardType::ShipObjective
+        );
+        assert_eq!(
+            hob_card_type("Encounter_Side_Quest"),
+            octgn::CardType::EncounterSideQuest
+        );
+        assert_eq!(
+            hob_card_type("Nightmare_Setup"),
+            octgn::CardType::Unknown("Nightmare_Setup".to_string())
+        );
+    }
+
+    #[test]
+    fn test_hob_to_octgn_set_round_trip() {
         let hob_cards = load_hall_of_beorn();
-        let fire_drake = guess_hob_card(&hob_cards, "Fire Drake");
-        assert_eq!(fire_drake.title, "Fire-drake");
+        let set = hob_to_octgn_set(
+            "The Wilds of Rhovanion",
+            &octgn::Game::LOTR,
+            None,
+            &hob_cards,
+        );
+
+        let xml = set.to_xml();
+        let doc = roxmltree::Document::parse(&xml).unwrap();
+        let parsed = octgn::Set::new(&doc, &octgn::Game::LOTR).unwrap();
+        assert_eq!(parsed.id, set.id);
+        assert_eq!(parsed.cards, set.cards);
+    }
+
+    #[test]
+    fn test_import_ringsdb_json() {
+        let _m = ringsdb_mocks::cards().unwrap();
+        let sets = vec![load_octgn_set()];
+
+        let (deck, unmatched) = import_ringsdb(
+            Path::new("fixtures/ringsdb/deck.json"),
+            &sets,
+            &octgn::Game::LOTR,
+        )
+        .unwrap();
+        assert_eq!(unmatched, vec!["1x Aragorn (Core Set)"]);
+
+        let heroes = deck.section("Hero").unwrap();
+        assert_eq!(heroes.cards.len(), 2);
+        assert_eq!(&heroes.cards[0].id, "2b75792d-5873-4fc6-9272-d20dd517d36b");
+        let allies = deck.section("Ally").unwrap();
+        assert_eq!(allies.cards[0].quantity, 3);
+        let attachments = deck.section("Attachment").unwrap();
+        assert_eq!(&attachments.cards[0].name, "Hauberk of Mail");
+        assert_eq!(attachments.cards[0].quantity, 2);
+        let sideboard = deck.section("Sideboard").unwrap();
+        assert_eq!(&sideboard.cards[0].name, "Bow of Yew");
+    }
+
+    #[test]
+    fn test_import_ringsdb_text() {
+        let sets = vec![load_octgn_set()];
+
+        let (deck, unmatched) = import_ringsdb(
+            Path::new("fixtures/ringsdb/deck.txt"),
+            &sets,
+            &octgn::Game::LOTR,
+        )
+        .unwrap();
+        assert_eq!(unmatched, vec!["1x Ring of Barahir (Core Set)"]);
+
+        assert_eq!(deck.section("Hero").unwrap().cards.len(), 2);
+        // pack name typo is still matched to the set
+        let attachments = deck.section("Attachment").unwrap();
+        assert_eq!(&attachments.cards[0].name, "Hauberk of Mail");
+        assert_eq!(deck.section("Sideboard").unwrap().cards.len(), 1);
+    }
+
+    #[test]
+    fn test_import_ringsdb_malformed() {
+        let tmp_dir = TempDir::new("lotr-test").unwrap();
+        let sets = vec![load_octgn_set()];
+
+        let truncated = tmp_dir.path().join("truncated.json");
+        std::fs::write(&truncated, r#"{"name": "Dale", "slots": {"11001": 1"#).unwrap();
+        match import_ringsdb(&truncated, &sets, &octgn::Game::LOTR) {
+            Err(err @ Error::Json(_)) => assert_eq!(err.exit_code(), 7),
+            _ => panic!("expected a truncated JSON deck to be a parse error"),
+        }
+
+        let empty = tmp_dir.path().join("empty.txt");
+        std::fs::write(&empty, "Not a deck\n").unwrap();
+        match import_ringsdb(&empty, &sets, &octgn::Game::LOTR) {
+            Err(err @ Error::Json(_)) => assert_eq!(err.exit_code(), 7),
+            _ => panic!("expected a text deck without cards to be a parse error"),
+        }
+    }
+
+    #[test]
+    fn test_ringsdb_to_octgn_card_guess() {
+        let sets = vec![load_octgn_set()];
+        let entry = ringsdb::DeckEntry {
+            name: "Redwater Sentries".to_string(),
+            pack_name: "The Wilds of Rhovanion".to_string(),
+            number: None,
+            quantity: 1,
+            sideboard: false,
+        };
+
+        let card = ringsdb_to_octgn_card(&entry, &sets).unwrap();
+        assert_eq!(&card.name, "Redwater Sentry");
+
+        // numbered like Bard son of Brand, but the title says otherwise
+        let renumbered = ringsdb::DeckEntry {
+            name: "Brand son of Bain".to_string(),
+            number: Some(2),
+            ..entry.clone()
+        };
+        let card = ringsdb_to_octgn_card(&renumbered, &sets).unwrap();
+        assert_eq!(&card.id, "2b75792d-5873-4fc6-9272-d20dd517d36b");
+        let numbered = ringsdb::DeckEntry {
+            name: "Redwater Sentries".to_string(),
+            number: Some(3),
+            ..entry.clone()
+        };
+        let card = ringsdb_to_octgn_card(&numbered, &sets).unwrap();
+        assert_eq!(&card.name, "Redwater Sentry");
+
+        // a card from another set isn't swapped for whatever is closest in this one
+        let entry = ringsdb::DeckEntry {
+            name: "Gandalf".to_string(),
+            ..entry
+        };
+        assert!(ringsdb_to_octgn_card(&entry, &sets).is_none());
+        let (_, unmatched) = ringsdb_to_octgn_deck(&[entry], &sets, &octgn::Game::LOTR).unwrap();
+        assert_eq!(unmatched, vec!["1x Gandalf (The Wilds of Rhovanion)"]);
+    }
+
+    #[test]
+    fn test_match_octgn_set_pinned() {
+        let octgn_sets = vec![load_octgn_set()];
+        let mut overrides = Overrides::default();
+        overrides.sets.insert(
+            "Wilds of Rhovanion".to_string(),
+            "The Wilds of Rhovanion".to_string(),
+        );
+        overrides.sets.insert(
+            "The Wilds of Rhovanion".to_string(),
+            "Wilds of Rhovanion".to_string(),
+        );
+
+        let set = match_octgn_set("Wilds of Rhovanion", &octgn_sets, &overrides).unwrap();
+        assert_eq!(&set.name, "The Wilds of Rhovanion");
+        // a pin wins over an exact name, even when it points nowhere
+        assert!(match_octgn_set("The Wilds of Rhovanion", &octgn_sets, &overrides).is_none());
     }
 
     #[test]
     fn test_sets() {
         let _m = hob_mocks::card_sets().unwrap();
         let dir = Path::new("fixtures/octgn/o8g/Sets");
-        let result = sets(&dir);
+        let result = sets(&dir, &octgn::Game::LOTR, None, &Overrides::default());
         assert!(result.is_ok());
 
-        let card_sets = result.unwrap();
+        let (card_sets, errors) = result.unwrap();
+        assert!(errors.is_empty());
         let mut title_check = HashMap::new();
         title_check.insert(0, "Core Set");
         title_check.insert(1, "The Hunt for Gollum");
@@ -431,4 +2025,22 @@ mod tests {
             assert_eq!(card_sets.get(index).unwrap().name, name);
         }
     }
+
+    #[test]
+    fn test_set_filter() {
+        let set = hob_to_octgn_set("Khazad-dûm", &octgn::Game::LOTR, None, &[]);
+        let hob_set = hall_of_beorn::CardSet {
+            name: "Khazad-dûm".to_string(),
+            cycle: Some("Dwarrowdelf".to_string()),
+            set_type: "Deluxe_Expansion".to_string(),
+        };
+
+        assert!(SetFilter::All.matches(&set, &hob_set));
+        assert!(SetFilter::Ids(vec!["other".to_string(), set.id.clone()]).matches(&set, &hob_set));
+        assert!(!SetFilter::Ids(vec!["other".to_string()]).matches(&set, &hob_set));
+        assert!(SetFilter::Cycle("dwarrowdelf".to_string()).matches(&set, &hob_set));
+        assert!(!SetFilter::Cycle("Against the Shadow".to_string()).matches(&set, &hob_set));
+        assert!(SetFilter::SetType("Deluxe_Expansion".to_string()).matches(&set, &hob_set));
+        assert!(!SetFilter::SetType("Adventure_Pack".to_string()).matches(&set, &hob_set));
+    }
 }
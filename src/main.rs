@@ -1,3 +1,4 @@
+use std::error::Error;
 use std::io::Write;
 
 use docopt::Docopt;
@@ -8,18 +9,173 @@ const USAGE: &str = "
 LotR OCTGN
 
 Usage:
-  lotr-octgn pack [--set=<id>]
-  lotr-octgn sets
+  lotr-octgn pack [--all | --set=<id>... | --cycle=<name> | --set-type=<type>] [--changed] [--max-downloads=<n>] [--timeout=<secs>] [--retries=<n>] [--offline] [--refresh] [--hob-ttl=<hours>] [--max-distance=<n>] [--min-similarity=<ratio>] [--placeholder=<image> | --strict] [--overrides=<file>]
+  lotr-octgn sets [--refresh] [--hob-ttl=<hours>] [--overrides=<file>]
+  lotr-octgn generate [--hob-set=<name>] [--max-downloads=<n>] [--timeout=<secs>] [--retries=<n>] [--refresh] [--hob-ttl=<hours>] [--overrides=<file>]
+  lotr-octgn match-report [--format=<format>] [--max-distance=<n>] [--min-similarity=<ratio>] [--offline] [--refresh] [--hob-ttl=<hours>] [--overrides=<file>]
+  lotr-octgn deck <file>
+  lotr-octgn build-deck <file> <card>...
+  lotr-octgn import-ringsdb <file> <output>
+  lotr-octgn cache ls
+  lotr-octgn cache prune [--older-than=<days>]
+  lotr-octgn cache clear
+
+Cards are given by name or GUID, optionally prefixed with a quantity like \"3x Redwater Sentry\".
 
 Options:
-  --set=<id>  OCTGN Set ID
+  --all                Pack every Set
+  --set=<id>           OCTGN Set ID, can be given more than once
+  --cycle=<name>       Pack every Set in a Hall of Beorn cycle, like \"Dwarrowdelf\"
+  --set-type=<type>    Pack every Set of a Hall of Beorn type, like \"Deluxe_Expansion\"
+  --changed            Only pack Sets that changed since they were last packed, from all Sets if
+                       none are given
+  --hob-set=<name>     Hall of Beorn Set name
+  --max-downloads=<n>  Most images to download at once [default: 8]
+  --timeout=<secs>     Seconds before an image download is abandoned [default: 30]
+  --retries=<n>        Times to retry a throttled or failed image download [default: 3]
+  --max-distance=<n>   Most edits between titles for a closest title match [default: 4]
+  --min-similarity=<ratio>
+                       Least similarity, from 0 to 1, for a closest title match [default: 0.75]
+  --placeholder=<image>
+                       Use this image for cards without a close enough match, instead of
+                       leaving them out
+  --strict             Fail if any card doesn't have a close enough match
+  --overrides=<file>   TOML or JSON file of card and Set matches to use instead of guessing,
+                       defaults to overrides.toml or overrides.json in ~/.lotr-octgn
+  --format=<format>    How to print the match report: table, json or csv [default: table]
+  --older-than=<days>  Prune cached images not used in this many days [default: 30]
+  --offline            Only use the cached OCTGN repo, Hall of Beorn data and images
+  --refresh            Fetch Hall of Beorn data again even if it's cached
+  --hob-ttl=<hours>    Hours to use cached Hall of Beorn data for [default: 24]
+
+Exit codes:
+  1   A set, deck or game definition file couldn't be parsed, or is for a different game
+  2   Couldn't find the requested Set
+  3   Couldn't build the image pack
+  4   Invalid command
+  5   A Hall of Beorn or RingsDB request failed
+  6   The Set # wasn't a number
+  7   Couldn't parse a RingsDB deck
+  8   Couldn't match cards
+  9   Couldn't read or write a file
+  10  Couldn't find a home directory for caching
+  11  Couldn't clone or update the OCTGN git repo
+  12  Running offline and something needed isn't cached
+  13  Couldn't parse the overrides file or pack history
 ";
 
 #[derive(Debug, Deserialize)]
 struct Args {
-    flag_set: Option<String>,
+    flag_all: bool,
+    flag_set: Vec<String>,
+    flag_cycle: Option<String>,
+    flag_set_type: Option<String>,
+    flag_changed: bool,
+    flag_hob_set: Option<String>,
+    flag_max_downloads: usize,
+    flag_timeout: u64,
+    flag_retries: u32,
+    flag_older_than: u64,
+    flag_offline: bool,
+    flag_refresh: bool,
+    flag_hob_ttl: u64,
+    flag_max_distance: usize,
+    flag_min_similarity: f64,
+    flag_placeholder: Option<String>,
+    flag_strict: bool,
+    flag_overrides: Option<String>,
+    flag_format: String,
+    arg_file: Option<String>,
+    arg_card: Vec<String>,
+    arg_output: Option<String>,
     cmd_pack: bool,
     cmd_sets: bool,
+    cmd_generate: bool,
+    cmd_match_report: bool,
+    cmd_deck: bool,
+    cmd_build_deck: bool,
+    cmd_import_ringsdb: bool,
+    cmd_cache: bool,
+    cmd_ls: bool,
+    cmd_prune: bool,
+    cmd_clear: bool,
+}
+
+/// Print `context` followed by every cause of `err`, then exit with the error's exit code.
+fn exit_with(context: &str, err: &lotr_octgn::Error) -> ! {
+    eprintln!("{}: {}", context, err);
+    let mut source = err.source();
+    while let Some(cause) = source {
+        eprintln!("  Caused by: {}", cause);
+        source = cause.source();
+    }
+
+    std::process::exit(err.exit_code());
+}
+
+/// Clone or update the OCTGN repo, then read the game definition its sets are made for.
+fn open_repo(
+    git_dir: &std::path::Path,
+    offline: bool,
+) -> (lotr_octgn::GitCache<'_>, lotr_octgn::octgn::GameDefinition) {
+    let git_cache = lotr_octgn::GitCache::new(lotr_octgn::OCTGN_GIT_URL.to_string(), git_dir);
+    git_cache
+        .update_or_fetch(offline)
+        .unwrap_or_else(|err| exit_with("Problem cloning git repo", &err));
+    let definition = lotr_octgn::octgn::GameDefinition::open(&git_cache.definition_file)
+        .unwrap_or_else(|err| exit_with("Couldn't read the game definition", &err));
+
+    (git_cache, definition)
+}
+
+/// The overrides file given with `--overrides`, otherwise the one in the app dir if there is one.
+fn load_overrides(path: Option<&String>, app_dir: &std::path::Path) -> lotr_octgn::Overrides {
+    match path {
+        Some(path) => Some(std::path::PathBuf::from(path)),
+        None => ["overrides.toml", "overrides.json"]
+            .iter()
+            .map(|file_name| app_dir.join(file_name))
+            .find(|path| path.is_file()),
+    }
+    .map(|path| {
+        lotr_octgn::Overrides::open(&path)
+            .unwrap_or_else(|err| exit_with("Couldn't read the overrides file", &err))
+    })
+    .unwrap_or_default()
+}
+
+/// `err` and its causes on one line, for warnings.
+fn error_chain(err: &Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+
+    message
+}
+
+/// Warn about set files that couldn't be loaded, which the command carries on without.
+fn warn_skipped_sets(errors: &[lotr_octgn::octgn::SetLoadError]) {
+    for (path, err) in errors {
+        eprintln!(
+            "Warning: Skipping Set '{}': {}",
+            path.display(),
+            error_chain(err)
+        );
+    }
+}
+
+/// Every set in the repo that loads, warning about the rest.
+fn load_sets(
+    sets_dir: &std::path::Path,
+    game: &lotr_octgn::octgn::Game,
+) -> Vec<lotr_octgn::octgn::Set> {
+    let (sets, errors) = lotr_octgn::octgn::Set::fetch_all_tolerant(sets_dir, game);
+    warn_skipped_sets(&errors);
+
+    sets
 }
 
 fn main() {
@@ -31,31 +187,263 @@ fn main() {
         std::process::exit(10);
     });
     let app_dir = home_dir.join(APP_DIR);
+    let image_cache = lotr_octgn::ImageCache::new(app_dir.join("images"));
+    let download_options = lotr_octgn::DownloadOptions {
+        max_concurrent: args.flag_max_downloads,
+        timeout: std::time::Duration::from_secs(args.flag_timeout),
+        retries: args.flag_retries,
+        cache: Some(image_cache.clone()),
+        offline: args.flag_offline,
+        ..lotr_octgn::DownloadOptions::default()
+    };
+    let match_options = lotr_octgn::MatchOptions {
+        max_distance: args.flag_max_distance,
+        min_similarity: args.flag_min_similarity,
+        unmatched: match (&args.flag_placeholder, args.flag_strict) {
+            (_, true) => lotr_octgn::UnmatchedAction::Fail,
+            (Some(image), _) => lotr_octgn::UnmatchedAction::Placeholder(image.into()),
+            (None, false) => lotr_octgn::UnmatchedAction::Skip,
+        },
+        overrides: lotr_octgn::Overrides::default(),
+    };
+    let hob_cache = lotr_octgn::hall_of_beorn::Cache {
+        ttl: std::time::Duration::from_secs(args.flag_hob_ttl * 60 * 60),
+        refresh: args.flag_refresh,
+        offline: args.flag_offline,
+        ..lotr_octgn::hall_of_beorn::Cache::new(app_dir.join("hob"))
+    };
 
     if args.cmd_pack {
+        let overrides = load_overrides(args.flag_overrides.as_ref(), &app_dir);
+        let match_options = lotr_octgn::MatchOptions {
+            overrides: overrides.clone(),
+            ..match_options
+        };
         let git_dir = app_dir.join("git").join("lotr");
-        let git_cache = lotr_octgn::GitCache::new(lotr_octgn::OCTGN_GIT_URL.to_string(), &git_dir);
-        git_cache.update_or_fetch().unwrap_or_else(|err| {
-            eprintln!("Problem cloning git repo: {}", err);
-            std::process::exit(11);
-        });
-
-        let sets = lotr_octgn::sets(&git_cache.sets_dir).unwrap_or_else(|err| {
-            eprintln!("Couldn't fetch Sets: {:?}", err);
-            std::process::exit(1);
-        });
-
-        let set = args
-            .flag_set
-            .map(|set_id| {
-                sets.iter().find(|set| set.id == set_id).unwrap_or_else(|| {
-                    eprintln!("Couldn't find that Set");
+        let (git_cache, definition) = open_repo(&git_dir, args.flag_offline);
+        let game = definition.game();
+
+        let (pairs, errors) =
+            lotr_octgn::paired_sets(&git_cache.sets_dir, &game, Some(&hob_cache), &overrides)
+                .unwrap_or_else(|err| exit_with("Couldn't fetch Sets", &err));
+        warn_skipped_sets(&errors);
+
+        let filter = if args.flag_all {
+            lotr_octgn::SetFilter::All
+        } else if let Some(cycle) = args.flag_cycle {
+            lotr_octgn::SetFilter::Cycle(cycle)
+        } else if let Some(set_type) = args.flag_set_type {
+            lotr_octgn::SetFilter::SetType(set_type)
+        } else if !args.flag_set.is_empty() {
+            for set_id in &args.flag_set {
+                if !pairs.iter().any(|(set, _)| &set.id == set_id) {
+                    eprintln!("Couldn't find Set '{}'", set_id);
                     std::process::exit(2);
+                }
+            }
+            lotr_octgn::SetFilter::Ids(args.flag_set)
+        } else if args.flag_changed {
+            lotr_octgn::SetFilter::All
+        } else {
+            // if no sets given, allow users to pick one from list of available
+            for (index, (set, _)) in pairs.iter().enumerate() {
+                println!("{}: {}", index, set.name);
+            }
+            print!("Input Set #: ");
+            std::io::stdout().flush().unwrap();
+            let mut buffer = String::new();
+            std::io::stdin().read_line(&mut buffer).unwrap();
+
+            let index = buffer.trim_end().parse::<usize>().unwrap_or_else(|_| {
+                eprintln!("Please specify a number: '{}'", buffer);
+                std::process::exit(6);
+            });
+
+            let (set, _) = pairs.get(index).unwrap_or_else(|| {
+                eprintln!("Couldn't find that Set");
+                std::process::exit(2);
+            });
+            lotr_octgn::SetFilter::Ids(vec![set.id.clone()])
+        };
+        let sets = pairs
+            .iter()
+            .filter(|(set, hob_set)| filter.matches(set, hob_set))
+            .map(|(set, _)| set)
+            .collect::<Vec<&lotr_octgn::octgn::Set>>();
+        if sets.is_empty() {
+            eprintln!("Couldn't find any matching Sets");
+            std::process::exit(2);
+        }
+
+        let history_file = app_dir.join("packs.json");
+        let mut history = lotr_octgn::PackHistory::open(&history_file)
+            .unwrap_or_else(|err| exit_with("Couldn't read the pack history", &err));
+        let head = git_cache
+            .head()
+            .unwrap_or_else(|err| exit_with("Couldn't read the git repo", &err));
+        let sets = if args.flag_changed {
+            let changed = history
+                .changed(&sets, &git_cache)
+                .unwrap_or_else(|err| exit_with("Couldn't check for changed Sets", &err));
+            if changed.is_empty() {
+                println!("No Sets changed since they were last packed");
+                return;
+            }
+            changed
+        } else {
+            sets
+        };
+
+        let mut failed = vec![];
+        for set in &sets {
+            for error in definition.validate(set) {
+                eprintln!("Warning: {}", error);
+            }
+            // every set being packed came from `pairs`
+            let (_, hob_set) = pairs
+                .iter()
+                .find(|(paired_set, _)| std::ptr::eq(paired_set, *set))
+                .unwrap();
+            match lotr_octgn::pack(
+                set,
+                &definition,
+                hob_set,
+                Some(&hob_cache),
+                &match_options,
+                &download_options,
+            ) {
+                Ok(()) => {
+                    if let Err(err) = history
+                        .record(set, &head)
+                        .and_then(|_| history.save(&history_file))
+                    {
+                        eprintln!(
+                            "Warning: Couldn't record the pack of '{}': {}",
+                            set.name,
+                            error_chain(&err)
+                        );
+                    }
+                }
+                Err(err) => {
+                    if sets.len() == 1 {
+                        exit_with("Couldn't pack Set", &err);
+                    }
+                    eprintln!("Couldn't pack Set '{}': {}", set.name, error_chain(&err));
+                    failed.push((set, err));
+                }
+            }
+        }
+
+        if sets.len() > 1 {
+            println!();
+            println!(
+                "Packed {} of {} Sets",
+                sets.len() - failed.len(),
+                sets.len()
+            );
+            for set in &sets {
+                match failed
+                    .iter()
+                    .find(|(failed_set, _)| failed_set.id == set.id)
+                {
+                    Some((_, err)) => println!("  failed  {}: {}", set.name, error_chain(err)),
+                    None => println!("  ok      {}", set.name),
+                }
+            }
+        }
+        if let Some((_, err)) = failed.first() {
+            std::process::exit(err.exit_code());
+        }
+    } else if args.cmd_sets {
+        let overrides = load_overrides(args.flag_overrides.as_ref(), &app_dir);
+        let git_dir = app_dir.join("git").join("lotr");
+        let (git_cache, definition) = open_repo(&git_dir, args.flag_offline);
+        let game = definition.game();
+        match lotr_octgn::sets(&git_cache.sets_dir, &game, Some(&hob_cache), &overrides) {
+            Ok((sets, errors)) => {
+                warn_skipped_sets(&errors);
+                for set in sets {
+                    println!("{}: {}", set.name, set.id);
+                    for error in definition.validate(&set) {
+                        eprintln!("Warning: {}", error);
+                    }
+                }
+            }
+            Err(err) => exit_with("Couldn't fetch Sets", &err),
+        }
+    } else if args.cmd_match_report {
+        if !["table", "json", "csv"].contains(&args.flag_format.as_str()) {
+            eprintln!(
+                "Unknown format '{}', use table, json or csv",
+                args.flag_format
+            );
+            std::process::exit(4);
+        }
+        let match_options = lotr_octgn::MatchOptions {
+            overrides: load_overrides(args.flag_overrides.as_ref(), &app_dir),
+            ..match_options
+        };
+        let git_dir = app_dir.join("git").join("lotr");
+        let (git_cache, definition) = open_repo(&git_dir, args.flag_offline);
+        let game = definition.game();
+
+        let (report, errors) =
+            lotr_octgn::match_report(&git_cache.sets_dir, &game, Some(&hob_cache), &match_options)
+                .unwrap_or_else(|err| exit_with("Couldn't match Sets", &err));
+        warn_skipped_sets(&errors);
+        match args.flag_format.as_str() {
+            "json" => println!(
+                "{}",
+                serde_json::to_string_pretty(&report).unwrap_or_else(|err| {
+                    exit_with(
+                        "Couldn't write the report",
+                        &lotr_octgn::Error::Io(err.into()),
+                    )
                 })
+            ),
+            "csv" => print!("{}", report.to_csv()),
+            _ => print!("{}", report.to_table()),
+        }
+        eprintln!(
+            "{} cards, {} not close enough, {} duplicates, {} Hall of Beorn cards unused",
+            report.cards.len(),
+            report
+                .cards
+                .iter()
+                .filter(|card| card.strategy == lotr_octgn::MatchStrategy::Unmatched)
+                .count(),
+            report.cards.iter().filter(|card| card.duplicate).count(),
+            report.unused.len()
+        );
+    } else if args.cmd_generate {
+        let overrides = load_overrides(args.flag_overrides.as_ref(), &app_dir);
+        let match_options = lotr_octgn::MatchOptions {
+            overrides: overrides.clone(),
+            ..match_options
+        };
+        let git_dir = app_dir.join("git").join("lotr");
+        let (git_cache, definition) = open_repo(&git_dir, args.flag_offline);
+        let game = definition.game();
+
+        let (hob_sets, game_version, errors) =
+            lotr_octgn::missing_sets(&git_cache.sets_dir, &game, Some(&hob_cache), &overrides)
+                .unwrap_or_else(|err| exit_with("Couldn't fetch Sets", &err));
+        warn_skipped_sets(&errors);
+
+        let hob_set = args
+            .flag_hob_set
+            .map(|set_name| {
+                hob_sets
+                    .iter()
+                    .find(|set| set.name == set_name)
+                    .unwrap_or_else(|| {
+                        eprintln!("Couldn't find that Set or it's already in OCTGN");
+                        std::process::exit(2);
+                    })
             })
             .unwrap_or_else(|| {
-                // if no set id provided, allow users to pick one from list of available
-                for (index, set) in sets.iter().enumerate() {
+                // if no set name provided, allow users to pick one from list of missing sets
+                for (index, set) in hob_sets.iter().enumerate() {
                     println!("{}: {}", index, set.name);
                 }
                 print!("Input Set #: ");
@@ -63,37 +451,118 @@ fn main() {
                 let mut buffer = String::new();
                 std::io::stdin().read_line(&mut buffer).unwrap();
 
-                let index = buffer.trim_right().parse::<usize>().unwrap_or_else(|_| {
+                let index = buffer.trim_end().parse::<usize>().unwrap_or_else(|_| {
                     eprintln!("Please specify a number: '{}'", buffer);
                     std::process::exit(6);
                 });
 
-                sets.get(index).unwrap_or_else(|| {
+                hob_sets.get(index).unwrap_or_else(|| {
                     eprintln!("Couldn't find that Set");
                     std::process::exit(2);
                 })
             });
-        lotr_octgn::pack(&set).unwrap_or_else(|_| {
-            std::process::exit(3);
-        });
-    } else if args.cmd_sets {
+        lotr_octgn::generate(
+            hob_set,
+            &definition,
+            game_version,
+            Some(&hob_cache),
+            &match_options,
+            &download_options,
+        )
+        .unwrap_or_else(|err| exit_with("Couldn't generate Set", &err));
+    } else if args.cmd_deck {
         let git_dir = app_dir.join("git").join("lotr");
-        let git_cache = lotr_octgn::GitCache::new(lotr_octgn::OCTGN_GIT_URL.to_string(), &git_dir);
-        git_cache.update_or_fetch().unwrap_or_else(|err| {
-            eprintln!("Problem cloning git repo: {}", err);
-            std::process::exit(11);
-        });
-        match lotr_octgn::sets(&git_cache.sets_dir) {
-            Ok(sets) => {
-                for set in sets {
-                    println!("{}: {}", set.name, set.id);
-                }
-            }
-            Err(_) => {
-                eprintln!("Couldn't fetch Sets");
-                std::process::exit(1);
-            }
+        let (git_cache, definition) = open_repo(&git_dir, args.flag_offline);
+        let game = definition.game();
+        let deck_file = args.arg_file.unwrap();
+        let deck = lotr_octgn::octgn::Deck::open(std::path::Path::new(&deck_file), &game)
+            .unwrap_or_else(|err| exit_with(&format!("Couldn't read Deck '{}'", deck_file), &err));
+        let sets = load_sets(&git_cache.sets_dir, &game);
+
+        let resolved = deck.resolve(&sets);
+        for card in &resolved.cards {
+            println!(
+                "{}: {}x {} ({})",
+                card.section.name, card.entry.quantity, card.card.name, card.set.name
+            );
+        }
+        for (section, entry) in &resolved.unresolved {
+            eprintln!(
+                "Warning: Could not find {} Card '{}' ({}).",
+                section.name, entry.name, entry.id
+            );
+        }
+    } else if args.cmd_build_deck {
+        let git_dir = app_dir.join("git").join("lotr");
+        let (git_cache, definition) = open_repo(&git_dir, args.flag_offline);
+        let game = definition.game();
+        let sets = load_sets(&git_cache.sets_dir, &game);
+
+        let cards = args
+            .arg_card
+            .iter()
+            .map(|card| lotr_octgn::octgn::parse_card_quantity(card))
+            .collect::<Vec<(String, u32)>>();
+        let deck = lotr_octgn::octgn::Deck::from_cards(&cards, &sets, &game)
+            .unwrap_or_else(|err| exit_with("Couldn't build Deck", &lotr_octgn::Error::from(err)));
+
+        let deck_file = args.arg_file.unwrap();
+        deck.write(std::path::Path::new(&deck_file))
+            .unwrap_or_else(|err| {
+                exit_with(
+                    &format!("Couldn't write Deck '{}'", deck_file),
+                    &lotr_octgn::Error::from(err),
+                )
+            });
+        println!("Wrote {}", deck_file);
+    } else if args.cmd_import_ringsdb {
+        let git_dir = app_dir.join("git").join("lotr");
+        let (git_cache, definition) = open_repo(&git_dir, args.flag_offline);
+        let game = definition.game();
+        let sets = load_sets(&git_cache.sets_dir, &game);
+
+        let ringsdb_file = args.arg_file.unwrap();
+        let (deck, unmatched) =
+            lotr_octgn::import_ringsdb(std::path::Path::new(&ringsdb_file), &sets, &game)
+                .unwrap_or_else(|err| {
+                    exit_with(
+                        &format!("Couldn't import RingsDB Deck '{}'", ringsdb_file),
+                        &err,
+                    )
+                });
+        for card in &unmatched {
+            eprintln!("Warning: Could not match '{}' to an OCTGN Card.", card);
+        }
+
+        let deck_file = args.arg_output.unwrap();
+        deck.write(std::path::Path::new(&deck_file))
+            .unwrap_or_else(|err| {
+                exit_with(
+                    &format!("Couldn't write Deck '{}'", deck_file),
+                    &lotr_octgn::Error::from(err),
+                )
+            });
+        println!("Wrote {}", deck_file);
+    } else if args.cmd_cache && args.cmd_ls {
+        let entries = image_cache
+            .entries()
+            .unwrap_or_else(|err| exit_with("Couldn't read the image cache", &err));
+        let mut total = 0;
+        for (entry, size) in &entries {
+            println!("{}: {} bytes", entry.url, size);
+            total += size;
         }
+        println!("{} images, {} bytes", entries.len(), total);
+    } else if args.cmd_cache && args.cmd_prune {
+        let max_age = std::time::Duration::from_secs(args.flag_older_than * 24 * 60 * 60);
+        let removed = image_cache
+            .prune(max_age)
+            .unwrap_or_else(|err| exit_with("Couldn't prune the image cache", &err));
+        println!("Removed {} files", removed);
+    } else if args.cmd_cache && args.cmd_clear {
+        image_cache
+            .clear()
+            .unwrap_or_else(|err| exit_with("Couldn't clear the image cache", &err));
     } else {
         eprintln!("Invalid Command");
         println!("{}", USAGE);
@@ -0,0 +1,233 @@
+use serde_derive::*;
+
+use crate::{hall_of_beorn, match_cards, octgn, MatchOptions, MatchStrategy};
+
+const HEADERS: [&str; 8] = [
+    "set",
+    "octgn_id",
+    "octgn_name",
+    "side",
+    "hob_title",
+    "strategy",
+    "distance",
+    "duplicate",
+];
+
+/// What one side of an OCTGN card was matched to.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CardReport {
+    /// The OCTGN set name.
+    pub set: String,
+    pub octgn_id: String,
+    pub octgn_name: String,
+    /// Whether this is a different card printed on the back of the OCTGN card.
+    pub back: bool,
+    /// The matched Hall of Beorn card, or when `Unmatched` the closest one.
+    pub hob_title: Option<String>,
+    pub strategy: MatchStrategy,
+    pub distance: usize,
+    /// Another OCTGN card in the set was matched to the same Hall of Beorn card.
+    pub duplicate: bool,
+}
+
+/// A Hall of Beorn card no OCTGN card was matched to.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct UnusedCard {
+    /// The OCTGN set name.
+    pub set: String,
+    pub hob_title: String,
+    pub number: u32,
+}
+
+/// How every card in some sets was matched, without downloading anything.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct MatchReport {
+    pub cards: Vec<CardReport>,
+    pub unused: Vec<UnusedCard>,
+}
+
+impl MatchReport {
+    /// Match the cards in one set and add them to the report.
+    pub fn add_set(
+        &mut self,
+        set_name: &str,
+        octgn_cards: &[octgn::Card],
+        hob_cards: &[hall_of_beorn::Card],
+        options: &MatchOptions,
+    ) {
+        let matches = match_cards(set_name, octgn_cards, hob_cards, options);
+        let index = |hob_card: &hall_of_beorn::Card| {
+            hob_cards
+                .iter()
+                .position(|card| std::ptr::eq(card, hob_card))
+        };
+        let mut uses = vec![0; hob_cards.len()];
+        for card_match in &matches {
+            if let Some(i) = card_match.matched().and_then(index) {
+                uses[i] += 1;
+            }
+        }
+
+        self.cards.extend(matches.iter().map(|card_match| {
+            CardReport {
+                set: set_name.to_string(),
+                octgn_id: card_match.octgn_id.clone(),
+                octgn_name: card_match.octgn_name.clone(),
+                back: card_match.back,
+                hob_title: card_match.hob_card.map(|hob_card| hob_card.title.clone()),
+                strategy: card_match.strategy,
+                distance: card_match.distance,
+                duplicate: card_match
+                    .matched()
+                    .and_then(index)
+                    .is_some_and(|i| uses[i] > 1),
+            }
+        }));
+        self.unused.extend(
+            hob_cards
+                .iter()
+                .zip(uses)
+                .filter(|(_, uses)| *uses == 0)
+                .map(|(hob_card, _)| UnusedCard {
+                    set: set_name.to_string(),
+                    hob_title: hob_card.title.clone(),
+                    number: hob_card.number,
+                }),
+        );
+    }
+
+    /// One row per matched card then one per unused Hall of Beorn card, whose strategy is
+    /// "unused".
+    fn rows(&self) -> Vec<Vec<String>> {
+        let cards = self.cards.iter().map(|card| {
+            vec![
+                card.set.clone(),
+                card.octgn_id.clone(),
+                card.octgn_name.clone(),
+                if card.back { "back" } else { "front" }.to_string(),
+                card.hob_title.clone().unwrap_or_default(),
+                card.strategy.to_string(),
+                card.distance.to_string(),
+                if card.duplicate { "yes" } else { "" }.to_string(),
+            ]
+        });
+        let unused = self.unused.iter().map(|card| {
+            vec![
+                card.set.clone(),
+                String::new(),
+                String::new(),
+                String::new(),
+                card.hob_title.clone(),
+                "unused".to_string(),
+                String::new(),
+                String::new(),
+            ]
+        });
+
+        cards.chain(unused).collect()
+    }
+
+    /// The report as a table with aligned columns.
+    pub fn to_table(&self) -> String {
+        let rows = self.rows();
+        let mut widths = HEADERS
+            .iter()
+            .map(|header| header.len())
+            .collect::<Vec<_>>();
+        for row in &rows {
+            for (width, field) in widths.iter_mut().zip(row) {
+                *width = (*width).max(field.chars().count());
+            }
+        }
+
+        let headers = HEADERS.iter().map(|header| header.to_string()).collect();
+        std::iter::once(headers)
+            .chain(rows)
+            .map(|row: Vec<String>| {
+                let line = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(field, width)| format!("{:width$}", field, width = width))
+                    .collect::<Vec<_>>()
+                    .join("  ");
+                format!("{}\n", line.trim_end())
+            })
+            .collect()
+    }
+
+    /// The report as CSV, with a header row.
+    pub fn to_csv(&self) -> String {
+        let headers = HEADERS.iter().map(|header| header.to_string()).collect();
+        std::iter::once(headers)
+            .chain(self.rows())
+            .map(|row: Vec<String>| {
+                let line = row
+                    .iter()
+                    .map(|field| csv_field(field))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                format!("{}\n", line)
+            })
+            .collect()
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tests::{load_hall_of_beorn, octgn_card, SET};
+
+    #[test]
+    fn test_add_set() {
+        let hob_cards = load_hall_of_beorn();
+        let octgn_cards = vec![
+            octgn_card("42a5a608-0699-4cd5-b69d-f7c3413cd5cd", "Fire Drake"),
+            octgn_card("a8b1e0b4-4b9e-4a2b-9a0a-3f8f1e2d6c11", "Fire-drake"),
+            octgn_card("0c7f2a3e-5d41-4f6b-8e9a-2b1c3d4e5f60", "Woodman Village"),
+        ];
+
+        let mut report = MatchReport::default();
+        report.add_set(SET, &octgn_cards, &hob_cards, &MatchOptions::default());
+
+        assert_eq!(report.cards.len(), 3);
+        assert!(report.cards[0].duplicate);
+        assert!(report.cards[1].duplicate);
+        assert_eq!(
+            report.cards[2].hob_title.as_ref().unwrap(),
+            "Woodmen Village"
+        );
+        assert_eq!(report.cards[2].strategy, MatchStrategy::Fuzzy);
+        assert!(!report.cards[2].duplicate);
+        assert_eq!(report.unused.len(), hob_cards.len() - 2);
+        assert!(!report
+            .unused
+            .iter()
+            .any(|card| card.hob_title == "Fire-drake"));
+
+        let csv = report.to_csv();
+        assert!(
+            csv.starts_with("set,octgn_id,octgn_name,side,hob_title,strategy,distance,duplicate\n")
+        );
+        assert!(csv.contains(
+            "The Wilds of Rhovanion,0c7f2a3e-5d41-4f6b-8e9a-2b1c3d4e5f60,Woodman Village,front,Woodmen Village,fuzzy,1,\n"
+        ));
+        assert_eq!(csv.lines().count(), 1 + 3 + report.unused.len());
+        assert_eq!(report.to_table().lines().count(), csv.lines().count());
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("Fire-drake"), "Fire-drake");
+        assert_eq!(csv_field("Brand, Son of Bain"), "\"Brand, Son of Bain\"");
+        assert_eq!(csv_field("\"Sting\""), "\"\"\"Sting\"\"\"");
+    }
+}
@@ -0,0 +1,434 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use rayon::prelude::*;
+use serde_derive::*;
+
+use crate::{hall_of_beorn, octgn, CardOverride, Overrides};
+
+mod normalize;
+
+pub use self::normalize::normalize_title;
+
+/// How an OCTGN card was paired with a Hall of Beorn card, from most to least trustworthy.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchStrategy {
+    /// Given in the overrides file.
+    Override,
+    /// Same card number in the same set.
+    Number,
+    /// Same title once normalized.
+    Title,
+    /// The closest normalized title.
+    Fuzzy,
+    /// Even the closest title was too far off to trust.
+    Unmatched,
+}
+
+impl fmt::Display for MatchStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MatchStrategy::Override => write!(f, "override"),
+            MatchStrategy::Number => write!(f, "number"),
+            MatchStrategy::Title => write!(f, "title"),
+            MatchStrategy::Fuzzy => write!(f, "fuzzy"),
+            MatchStrategy::Unmatched => write!(f, "unmatched"),
+        }
+    }
+}
+
+/// What to do with an OCTGN card that has no close enough Hall of Beorn card.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum UnmatchedAction {
+    /// Leave the card out of the pack.
+    Skip,
+    /// Use this image for it instead.
+    Placeholder(PathBuf),
+    /// Don't build the pack.
+    Fail,
+}
+
+/// How OCTGN cards are matched to Hall of Beorn cards.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchOptions {
+    /// The most edits between normalized titles for a fuzzy match to be trusted.
+    pub max_distance: usize,
+    /// The least similar, from 0 to 1, normalized titles can be for a fuzzy match to be trusted.
+    pub min_similarity: f64,
+    pub unmatched: UnmatchedAction,
+    /// Consulted before anything else.
+    pub overrides: Overrides,
+}
+
+impl Default for MatchOptions {
+    fn default() -> Self {
+        MatchOptions {
+            max_distance: 4,
+            min_similarity: 0.75,
+            unmatched: UnmatchedAction::Skip,
+            overrides: Overrides::default(),
+        }
+    }
+}
+
+impl MatchOptions {
+    /// Whether `title` is close enough to `name` to use as their closest title match.
+    pub(crate) fn accepts(&self, name: &str, title: &str, distance: usize) -> bool {
+        let similarity =
+            strsim::normalized_levenshtein(&normalize_title(name), &normalize_title(title));
+
+        distance <= self.max_distance && similarity >= self.min_similarity
+    }
+}
+
+/// The Hall of Beorn card found for one side of an OCTGN card.
+#[derive(Clone)]
+pub struct CardMatch<'a> {
+    /// The OCTGN card's GUID.
+    pub octgn_id: String,
+    pub octgn_name: String,
+    /// Whether this is a different card printed on the back of the OCTGN card.
+    pub back: bool,
+    /// The matched card, or when `Unmatched` the closest one that was turned down. `None` when
+    /// an override gives only an image, or there are no Hall of Beorn cards at all.
+    pub hob_card: Option<&'a hall_of_beorn::Card>,
+    pub strategy: MatchStrategy,
+    /// Edit distance between the normalized titles, always 0 when matched by title.
+    pub distance: usize,
+    /// An image URL or local path from the overrides file, used instead of Hall of Beorn's.
+    pub image_override: Option<String>,
+}
+
+impl<'a> CardMatch<'a> {
+    /// The Hall of Beorn card to use, unless there wasn't a close enough one.
+    pub fn matched(&self) -> Option<&'a hall_of_beorn::Card> {
+        match self.strategy {
+            MatchStrategy::Unmatched => None,
+            _ => self.hob_card,
+        }
+    }
+
+    /// The front image to use, unless there wasn't a close enough card.
+    pub fn image_url(&self) -> Option<String> {
+        self.image_override.clone().or_else(|| {
+            self.matched()
+                .map(|hob_card| hob_card.front.image_path.to_owned())
+        })
+    }
+}
+
+/// Finds Hall of Beorn cards from one set by number, title or closest title.
+pub(crate) struct CardMatcher<'a> {
+    hob_cards: &'a [hall_of_beorn::Card],
+    by_number: HashMap<u32, Vec<&'a hall_of_beorn::Card>>,
+    /// Cards by normalized title.
+    by_title: HashMap<String, &'a hall_of_beorn::Card>,
+}
+
+impl<'a> CardMatcher<'a> {
+    pub fn new(hob_cards: &'a [hall_of_beorn::Card]) -> Self {
+        let mut by_number = HashMap::new();
+        let mut by_title = HashMap::new();
+        for hob_card in hob_cards {
+            by_number
+                .entry(hob_card.number)
+                .or_insert_with(Vec::new)
+                .push(hob_card);
+            by_title
+                .entry(normalize_title(&hob_card.title))
+                .or_insert(hob_card);
+        }
+
+        CardMatcher {
+            hob_cards,
+            by_number,
+            by_title,
+        }
+    }
+
+    /// The Hall of Beorn card for an OCTGN card side, how it was found and how far its title is
+    /// from `name`. A card with the same number but a title too far off for `options` is passed
+    /// over, since the number is more likely wrong than the title. Only `None` when there are no
+    /// Hall of Beorn cards at all.
+    pub fn find(
+        &self,
+        name: &str,
+        number: Option<u32>,
+        options: &MatchOptions,
+    ) -> Option<(&'a hall_of_beorn::Card, MatchStrategy, usize)> {
+        let title = normalize_title(name);
+        let by_number = number
+            .and_then(|number| self.by_number.get(&number))
+            .and_then(|candidates| match candidates.as_slice() {
+                [hob_card] => {
+                    let distance = title_distance(name, &hob_card.title);
+                    Some((*hob_card, distance))
+                        .filter(|_| options.accepts(name, &hob_card.title, distance))
+                }
+                // several cards share the number, so the title has to decide
+                _ => candidates
+                    .iter()
+                    .find(|hob_card| normalize_title(&hob_card.title) == title)
+                    .map(|hob_card| (*hob_card, 0)),
+            });
+        if let Some((hob_card, distance)) = by_number {
+            return Some((hob_card, MatchStrategy::Number, distance));
+        }
+        if let Some(hob_card) = self.by_title.get(&title) {
+            return Some((hob_card, MatchStrategy::Title, 0));
+        }
+
+        guess_hob_card(self.hob_cards, name)
+            .map(|(hob_card, distance)| (hob_card, MatchStrategy::Fuzzy, distance))
+    }
+
+    /// Apply an override for a card called `name`. A title that isn't in the set is `Unmatched`
+    /// rather than guessed at, since whoever wrote it meant that exact card.
+    fn find_override(
+        &self,
+        name: &str,
+        card_override: &CardOverride,
+    ) -> (Option<&'a hall_of_beorn::Card>, MatchStrategy, usize) {
+        let title = match &card_override.title {
+            Some(title) => title,
+            None => return (None, MatchStrategy::Override, 0),
+        };
+        match self.by_title.get(&normalize_title(title)) {
+            Some(hob_card) => (Some(*hob_card), MatchStrategy::Override, 0),
+            None => match guess_hob_card(self.hob_cards, name) {
+                Some((hob_card, distance)) => (Some(hob_card), MatchStrategy::Unmatched, distance),
+                None => (None, MatchStrategy::Unmatched, 0),
+            },
+        }
+    }
+}
+
+/// Match every OCTGN card in a set, and every different card printed on the back of one, to a
+/// Hall of Beorn card from the same set. Overrides come first, and fuzzy matches too far off for
+/// `options` are `Unmatched`.
+pub fn match_cards<'a>(
+    set_name: &str,
+    octgn_cards: &[octgn::Card],
+    hob_cards: &'a [hall_of_beorn::Card],
+    options: &MatchOptions,
+) -> Vec<CardMatch<'a>> {
+    let matcher = CardMatcher::new(hob_cards);
+
+    octgn_cards
+        .par_iter()
+        .flat_map(|octgn_card| {
+            let mut sides = vec![(&octgn_card.name, octgn_card.properties.number, false)];
+            if let Some(back) = octgn_card.back() {
+                if !octgn_card.is_same_card(back) {
+                    sides.push((&back.name, back.properties.number, true));
+                }
+            }
+
+            sides
+                .into_iter()
+                .filter_map(|(name, number, back)| {
+                    // only the front goes by the card's GUID
+                    let id = Some(octgn_card.id.as_str()).filter(|_| !back);
+                    let card_override = options.overrides.card(set_name, id, name);
+                    let (hob_card, strategy, distance) = match card_override {
+                        Some(card_override) => matcher.find_override(name, card_override),
+                        None => {
+                            let (hob_card, mut strategy, distance) =
+                                matcher.find(name, number, options)?;
+                            if strategy == MatchStrategy::Fuzzy
+                                && !options.accepts(name, &hob_card.title, distance)
+                            {
+                                strategy = MatchStrategy::Unmatched;
+                            }
+
+                            (Some(hob_card), strategy, distance)
+                        }
+                    };
+
+                    Some(CardMatch {
+                        octgn_id: octgn_card.id.to_string(),
+                        octgn_name: name.to_string(),
+                        back,
+                        hob_card,
+                        strategy,
+                        distance,
+                        image_override: card_override
+                            .and_then(|card_override| card_override.image.clone()),
+                    })
+                })
+                .collect::<Vec<CardMatch>>()
+        })
+        .collect()
+}
+
+/// The Hall of Beorn card with the title closest to `unknown_card_name`, and how close it is
+/// once both are normalized.
+pub(crate) fn guess_hob_card<'a>(
+    hob_cards: &'a [hall_of_beorn::Card],
+    unknown_card_name: &str,
+) -> Option<(&'a hall_of_beorn::Card, usize)> {
+    let name = normalize_title(unknown_card_name);
+
+    hob_cards
+        .par_iter()
+        .map(|hob_card| {
+            (
+                hob_card,
+                strsim::levenshtein(&name, &normalize_title(&hob_card.title)),
+            )
+        })
+        .min_by_key(|&(_, value)| value)
+}
+
+/// Edit distance between two titles once they're normalized.
+pub(crate) fn title_distance(a: &str, b: &str) -> usize {
+    strsim::levenshtein(&normalize_title(a), &normalize_title(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tests::{load_hall_of_beorn, octgn_card, SET};
+
+    const CARD_ID: &str = "42a5a608-0699-4cd5-b69d-f7c3413cd5cd";
+
+    #[test]
+    fn test_guess_hob_card() {
+        let hob_cards = load_hall_of_beorn();
+        let (fire_drake, distance) = guess_hob_card(&hob_cards, "Fire Drake").unwrap();
+        assert_eq!(fire_drake.title, "Fire-drake");
+        assert_eq!(distance, 0);
+
+        let (woodmen_village, distance) = guess_hob_card(&hob_cards, "Woodman Village").unwrap();
+        assert_eq!(woodmen_village.title, "Woodmen Village");
+        assert_eq!(distance, 1);
+    }
+
+    #[test]
+    fn test_match_cards_strategies() {
+        let hob_cards = load_hall_of_beorn();
+        let mut octgn_cards = vec![
+            octgn_card(CARD_ID, "Redwater Sentries"),
+            octgn_card(CARD_ID, "Brand son of Bain"),
+            octgn_card(CARD_ID, "Brand, Son of Bain"),
+            octgn_card(CARD_ID, "Fire Drake"),
+            octgn_card(CARD_ID, "Woodman Village"),
+        ];
+        // the number wins over a title that's only a little off
+        octgn_cards[0].properties.number = Some(3);
+        // numbered like Bard son of Brand, but the title is too far off to trust the number
+        octgn_cards[1].properties.number = Some(2);
+
+        let matches = match_cards(SET, &octgn_cards, &hob_cards, &MatchOptions::default());
+        let found = matches
+            .iter()
+            .map(|card_match| {
+                (
+                    card_match.hob_card.unwrap().title.as_str(),
+                    card_match.strategy,
+                    card_match.distance,
+                )
+            })
+            .collect::<Vec<(&str, MatchStrategy, usize)>>();
+        assert_eq!(
+            found,
+            vec![
+                ("Redwater Sentry", MatchStrategy::Number, 3),
+                ("Brand son of Bain", MatchStrategy::Title, 0),
+                ("Brand son of Bain", MatchStrategy::Title, 0),
+                ("Fire-drake", MatchStrategy::Title, 0),
+                ("Woodmen Village", MatchStrategy::Fuzzy, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_match_cards_unknown_number() {
+        let hob_cards = load_hall_of_beorn();
+        let mut octgn_cards = vec![octgn_card(CARD_ID, "Brand son of Bain")];
+        octgn_cards[0].properties.number = Some(999);
+
+        let matches = match_cards(SET, &octgn_cards, &hob_cards, &MatchOptions::default());
+        assert_eq!(matches[0].hob_card.unwrap().title, "Brand son of Bain");
+        assert_eq!(matches[0].strategy, MatchStrategy::Title);
+    }
+
+    #[test]
+    fn test_match_cards_unmatched() {
+        let hob_cards = load_hall_of_beorn();
+        let octgn_cards = vec![
+            octgn_card(CARD_ID, "Woodman Village"),
+            octgn_card(CARD_ID, "Gandalf"),
+        ];
+
+        let matches = match_cards(SET, &octgn_cards, &hob_cards, &MatchOptions::default());
+        assert_eq!(matches[0].strategy, MatchStrategy::Fuzzy);
+        assert_eq!(matches[1].strategy, MatchStrategy::Unmatched);
+        assert!(matches[1].matched().is_none());
+
+        let strict = MatchOptions {
+            max_distance: 0,
+            ..MatchOptions::default()
+        };
+        let matches = match_cards(SET, &octgn_cards, &hob_cards, &strict);
+        assert_eq!(matches[0].strategy, MatchStrategy::Unmatched);
+
+        let lenient = MatchOptions {
+            max_distance: 100,
+            min_similarity: 0.0,
+            ..MatchOptions::default()
+        };
+        let matches = match_cards(SET, &octgn_cards, &hob_cards, &lenient);
+        assert_eq!(matches[1].strategy, MatchStrategy::Fuzzy);
+    }
+
+    #[test]
+    fn test_match_cards_overrides() {
+        let hob_cards = load_hall_of_beorn();
+        // distinct GUIDs, so only the first card is overridden by its GUID
+        let mut octgn_cards = vec![
+            octgn_card(CARD_ID, "Brand son of Bain"),
+            octgn_card("b3a9a4d5-4f2c-4d7e-8a57-6d1d1c6b2f10", "Woodman Village"),
+            octgn_card("c6e2f0a1-9b8d-4f3a-a1c4-0e5d7b9f3a22", "Gandalf"),
+        ];
+        octgn_cards[0].properties.number = Some(1);
+        let mut options = MatchOptions::default();
+        options.overrides.cards.insert(
+            octgn_cards[0].id.to_string(),
+            CardOverride {
+                title: Some("Bard son of Brand".to_string()),
+                image: None,
+            },
+        );
+        let mut set_cards = HashMap::new();
+        set_cards.insert(
+            "Gandalf".to_string(),
+            CardOverride {
+                title: None,
+                image: Some("gandalf.jpg".to_string()),
+            },
+        );
+        set_cards.insert(
+            "Woodman Village".to_string(),
+            CardOverride {
+                title: Some("Not in the set".to_string()),
+                image: None,
+            },
+        );
+        options
+            .overrides
+            .set_cards
+            .insert(SET.to_string(), set_cards);
+
+        let matches = match_cards(SET, &octgn_cards, &hob_cards, &options);
+        assert_eq!(matches[0].strategy, MatchStrategy::Override);
+        assert_eq!(matches[0].hob_card.unwrap().title, "Bard son of Brand");
+        assert_eq!(matches[1].strategy, MatchStrategy::Unmatched);
+        assert!(matches[1].image_url().is_none());
+        assert_eq!(matches[2].strategy, MatchStrategy::Override);
+        assert_eq!(matches[2].image_url().unwrap(), "gandalf.jpg");
+    }
+}
@@ -0,0 +1,58 @@
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Articles dropped from the start of a title, so "The Voice of Isengard" is "Voice of Isengard".
+const ARTICLES: [&str; 3] = ["the", "a", "an"];
+
+/// A card or set title reduced to what's worth comparing: no accents, lowercase, words separated
+/// by single spaces with punctuation and hyphens dropped, and no leading article.
+///
+/// "Khazad-dûm" becomes "khazad dum" and "Fire-drake" becomes "fire drake". Titles with "son of"
+/// agree however they're punctuated or cased, so "Brand, Son of Bain" is "brand son of bain".
+pub fn normalize_title(title: &str) -> String {
+    let folded = title
+        .nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        // "Beorn's" and "Beorn’s" are the same word, not "beorn s"
+        .filter(|c| *c != '\'' && *c != '’')
+        .flat_map(|c| c.to_lowercase())
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>();
+    let mut words = folded.split_whitespace().collect::<Vec<&str>>();
+    if words.len() > 1 && ARTICLES.contains(&words[0]) {
+        words.remove(0);
+    }
+
+    words.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_title() {
+        assert_eq!(normalize_title("Khazad-dûm"), "khazad dum");
+        assert_eq!(normalize_title("Khazad-dum"), normalize_title("Khazad-dûm"));
+        assert_eq!(
+            normalize_title("The Voice of Isengard"),
+            "voice of isengard"
+        );
+        assert_eq!(normalize_title("Fire-drake"), normalize_title("Fire Drake"));
+        assert_eq!(
+            normalize_title("Brand, Son of Bain"),
+            normalize_title("Brand son of Bain")
+        );
+        assert_eq!(
+            normalize_title("The Hobbit: Over Hill and Under Hill"),
+            "hobbit over hill and under hill"
+        );
+        assert_eq!(
+            normalize_title("Beorn’s Path"),
+            normalize_title("Beorn's Path")
+        );
+        assert_eq!(normalize_title("Éowyn"), "eowyn");
+        // a lone article is the whole title
+        assert_eq!(normalize_title("The"), "the");
+    }
+}
@@ -2,12 +2,32 @@ use std::collections::HashMap;
 use std::fmt;
 use std::fs::File;
 use std::io::Read;
+use std::path::PathBuf;
 
 use roxmltree::Document;
 use walkdir::WalkDir;
 
+use crate::Error;
+
+mod deck;
+mod definition;
+mod pack;
+mod writer;
+
+pub use self::deck::{
+    parse_card_quantity, CardsNotFoundError, Deck, DeckCard, ResolvedCard, ResolvedDeck, Section,
+};
+pub use self::definition::{
+    CardDefinitionError, CardDefinitionErrorKind, GameDefinition, PropertyDefinition, PropertyType,
+    SizeDefinition,
+};
+pub use self::pack::{Include, Marker, Pack, PackItem, PackOption, Pick, Quantity};
+
 pub const LOTR_ID: &str = "a21af4e8-be4b-4cda-a6b6-534f9717391f";
 
+/// A set file that couldn't be loaded, and why.
+pub type SetLoadError = (PathBuf, Error);
+
 #[derive(Debug)]
 pub struct NoMatchingGameError {
     game_id: String,
@@ -29,17 +49,18 @@ impl fmt::Display for NoMatchingGameError {
     }
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Game {
     LOTR,
+    /// Any other game, identified by the id from its `definition.xml`.
+    Other(String),
 }
 
 impl Game {
-    fn from(s: &str) -> Option<Self> {
-        if s == LOTR_ID {
-            Some(Game::LOTR)
-        } else {
-            None
+    pub fn id(&self) -> &str {
+        match self {
+            Game::LOTR => LOTR_ID,
+            Game::Other(id) => id,
         }
     }
 }
@@ -92,11 +113,416 @@ impl AttributeMissingError {
     }
 }
 
+/// An attribute whose value couldn't be parsed, like a `<size>` with a width that isn't a number.
+#[derive(Debug, Eq, PartialEq)]
+pub struct InvalidAttributeError {
+    tag: String,
+    attribute: String,
+    value: String,
+}
+
+impl std::error::Error for InvalidAttributeError {}
+
+impl fmt::Display for InvalidAttributeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "<{}> has an invalid '{}': '{}'.",
+            self.tag, self.attribute, self.value
+        )
+    }
+}
+
+impl InvalidAttributeError {
+    pub fn new(tag: &str, attribute: &str, value: &str) -> Self {
+        Self {
+            tag: tag.to_string(),
+            attribute: attribute.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
+
+/// A `<card>` or `<alternate>` missing a required attribute, along with where it is and which
+/// card it belongs to.
+#[derive(Debug, Eq, PartialEq)]
+pub struct CardParseError {
+    /// The set file, when the set was loaded from disk.
+    pub path: Option<PathBuf>,
+    pub line: u32,
+    pub column: u32,
+    pub set_name: String,
+    pub card_id: Option<String>,
+    pub card_name: Option<String>,
+    pub error: AttributeMissingError,
+}
+
+impl std::error::Error for CardParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl fmt::Display for CardParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}:", path.display())?;
+        }
+        write!(f, "{}:{}: {} ", self.line, self.column, self.error)?;
+        match (&self.card_name, &self.card_id) {
+            (Some(name), Some(id)) => write!(f, "(card '{}' {}", name, id)?,
+            (Some(name), None) => write!(f, "(card '{}'", name)?,
+            (None, Some(id)) => write!(f, "(card {}", id)?,
+            (None, None) => write!(f, "(unnamed card")?,
+        }
+        write!(f, " in Set '{}')", self.set_name)
+    }
+}
+
+impl CardParseError {
+    fn new(
+        card_node: roxmltree::Node,
+        node: roxmltree::Node,
+        set_name: &str,
+        attribute: &str,
+    ) -> Self {
+        let pos = node.node_pos();
+
+        Self {
+            path: None,
+            line: pos.row,
+            column: pos.col,
+            set_name: set_name.to_string(),
+            card_id: card_node.attribute("id").map(|id| id.to_string()),
+            card_name: card_node.attribute("name").map(|name| name.to_string()),
+            error: AttributeMissingError::new(node.tag_name().name(), attribute),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub enum CardType {
+    Hero,
+    Ally,
+    Attachment,
+    Event,
+    PlayerSideQuest,
+    Contract,
+    Treasure,
+    Quest,
+    Enemy,
+    Location,
+    Treachery,
+    Objective,
+    ObjectiveAlly,
+    ObjectiveHero,
+    ObjectiveLocation,
+    ShipEnemy,
+    ShipObjective,
+    EncounterSideQuest,
+    Campaign,
+    Boon,
+    Burden,
+    Nightmare,
+    Unknown(String),
+}
+
+impl CardType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            CardType::Hero => "Hero",
+            CardType::Ally => "Ally",
+            CardType::Attachment => "Attachment",
+            CardType::Event => "Event",
+            CardType::PlayerSideQuest => "Player Side Quest",
+            CardType::Contract => "Contract",
+            CardType::Treasure => "Treasure",
+            CardType::Quest => "Quest",
+            CardType::Enemy => "Enemy",
+            CardType::Location => "Location",
+            CardType::Treachery => "Treachery",
+            CardType::Objective => "Objective",
+            CardType::ObjectiveAlly => "Objective Ally",
+            CardType::ObjectiveHero => "Objective Hero",
+            CardType::ObjectiveLocation => "Objective Location",
+            CardType::ShipEnemy => "Ship-Enemy",
+            CardType::ShipObjective => "Ship-Objective",
+            CardType::EncounterSideQuest => "Encounter Side Quest",
+            CardType::Campaign => "Campaign",
+            CardType::Boon => "Boon",
+            CardType::Burden => "Burden",
+            CardType::Nightmare => "Nightmare",
+            CardType::Unknown(s) => s,
+        }
+    }
+
+    /// Whether this type of card goes in a player's deck rather than the encounter deck.
+    pub fn is_player(&self) -> bool {
+        matches!(
+            self,
+            CardType::Hero
+                | CardType::Ally
+                | CardType::Attachment
+                | CardType::Event
+                | CardType::PlayerSideQuest
+                | CardType::Contract
+                | CardType::Treasure
+        )
+    }
+}
+
+impl From<&str> for CardType {
+    fn from(s: &str) -> Self {
+        match s {
+            "Hero" => CardType::Hero,
+            "Ally" => CardType::Ally,
+            "Attachment" => CardType::Attachment,
+            "Event" => CardType::Event,
+            "Player Side Quest" => CardType::PlayerSideQuest,
+            "Contract" => CardType::Contract,
+            "Treasure" => CardType::Treasure,
+            "Quest" => CardType::Quest,
+            "Enemy" => CardType::Enemy,
+            "Location" => CardType::Location,
+            "Treachery" => CardType::Treachery,
+            "Objective" => CardType::Objective,
+            "Objective Ally" => CardType::ObjectiveAlly,
+            "Objective Hero" => CardType::ObjectiveHero,
+            "Objective Location" => CardType::ObjectiveLocation,
+            "Ship-Enemy" => CardType::ShipEnemy,
+            "Ship-Objective" => CardType::ShipObjective,
+            "Encounter Side Quest" => CardType::EncounterSideQuest,
+            "Campaign" => CardType::Campaign,
+            "Boon" => CardType::Boon,
+            "Burden" => CardType::Burden,
+            "Nightmare" => CardType::Nightmare,
+            _ => CardType::Unknown(s.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for CardType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub enum Sphere {
+    Leadership,
+    Lore,
+    Spirit,
+    Tactics,
+    Neutral,
+    Baggins,
+    Fellowship,
+    Unknown(String),
+}
+
+impl Sphere {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Sphere::Leadership => "Leadership",
+            Sphere::Lore => "Lore",
+            Sphere::Spirit => "Spirit",
+            Sphere::Tactics => "Tactics",
+            Sphere::Neutral => "Neutral",
+            Sphere::Baggins => "Baggins",
+            Sphere::Fellowship => "Fellowship",
+            Sphere::Unknown(s) => s,
+        }
+    }
+}
+
+impl From<&str> for Sphere {
+    fn from(s: &str) -> Self {
+        match s {
+            "Leadership" => Sphere::Leadership,
+            "Lore" => Sphere::Lore,
+            "Spirit" => Sphere::Spirit,
+            "Tactics" => Sphere::Tactics,
+            "Neutral" => Sphere::Neutral,
+            "Baggins" => Sphere::Baggins,
+            "Fellowship" => Sphere::Fellowship,
+            _ => Sphere::Unknown(s.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Sphere {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// The `size` attribute of a `<card>`. Player cards don't set one and use the game's default size.
+#[derive(Clone, Debug, Default, Hash, Eq, PartialEq)]
+pub enum CardSize {
+    #[default]
+    Default,
+    EncounterCard,
+    QuestCard,
+    Unknown(String),
+}
+
+impl CardSize {
+    pub fn as_str(&self) -> &str {
+        match self {
+            CardSize::Default => "Default",
+            CardSize::EncounterCard => "EncounterCard",
+            CardSize::QuestCard => "QuestCard",
+            CardSize::Unknown(s) => s,
+        }
+    }
+}
+
+impl From<&str> for CardSize {
+    fn from(s: &str) -> Self {
+        match s {
+            "Default" => CardSize::Default,
+            "EncounterCard" => CardSize::EncounterCard,
+            "QuestCard" => CardSize::QuestCard,
+            _ => CardSize::Unknown(s.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for CardSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// The `<property>` elements of a card, both as typed fields and as the raw name/value pairs in
+/// document order.
+#[derive(Clone, Debug, Default, Hash, Eq, PartialEq)]
+pub struct Properties {
+    pub number: Option<u32>,
+    pub quantity: Option<u32>,
+    pub encounter_set: Option<String>,
+    pub card_type: Option<CardType>,
+    pub sphere: Option<Sphere>,
+    pub traits: Vec<String>,
+    pub keywords: Vec<String>,
+    pub cost: Option<u32>,
+    pub engagement_cost: Option<u32>,
+    pub threat: Option<u32>,
+    pub willpower: Option<u32>,
+    pub attack: Option<u32>,
+    pub defense: Option<u32>,
+    pub health: Option<u32>,
+    pub quest_points: Option<u32>,
+    pub victory_points: Option<u32>,
+    pub text: Option<String>,
+    pub shadow: Option<String>,
+    pub raw: Vec<(String, String)>,
+}
+
+impl Properties {
+    fn from_node(node: roxmltree::Node) -> Self {
+        let raw = node
+            .children()
+            .filter(|child| child.is_element() && child.tag_name().name() == "property")
+            .filter_map(|property_node| {
+                let atts = attributes(property_node.attributes());
+                let name = atts.get("name")?;
+                let value = atts.get("value").unwrap_or(&"");
+
+                Some((name.to_string(), value.to_string()))
+            })
+            .collect();
+
+        Properties::from_raw(raw)
+    }
+
+    pub fn from_raw(raw: Vec<(String, String)>) -> Self {
+        let mut properties = Properties::default();
+
+        for (name, value) in &raw {
+            match name.as_str() {
+                "Card Number" => properties.number = value.parse().ok(),
+                "Quantity" => properties.quantity = value.parse().ok(),
+                "Encounter Set" => properties.encounter_set = Some(value.to_string()),
+                "Type" => properties.card_type = Some(CardType::from(value.as_str())),
+                "Sphere" => properties.sphere = Some(Sphere::from(value.as_str())),
+                "Traits" => properties.traits = split_list(value),
+                "Keywords" => properties.keywords = split_list(value),
+                "Cost" => properties.cost = value.parse().ok(),
+                "Engagement Cost" => properties.engagement_cost = value.parse().ok(),
+                "Threat" => properties.threat = value.parse().ok(),
+                "Willpower" => properties.willpower = value.parse().ok(),
+                "Attack" => properties.attack = value.parse().ok(),
+                "Defense" => properties.defense = value.parse().ok(),
+                "Health" => properties.health = value.parse().ok(),
+                "Quest Points" => properties.quest_points = value.parse().ok(),
+                "Victory Points" => properties.victory_points = value.parse().ok(),
+                "Text" => properties.text = Some(value.to_string()),
+                "Shadow" => properties.shadow = Some(value.to_string()),
+                _ => (),
+            }
+        }
+        properties.raw = raw;
+
+        properties
+    }
+
+    /// Look up the raw value of a property, e.g. "Engagement Cost" which can hold a quest stage
+    /// letter instead of a number.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.raw
+            .iter()
+            .find(|(property, _)| property == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// An `<alternate>` side of a card, e.g. the B side of a quest card.
+#[derive(Debug, Hash, Eq, PartialEq)]
+pub struct CardSide {
+    pub alternate_type: String,
+    pub name: String,
+    pub properties: Properties,
+}
+
 #[derive(Debug, Hash, Eq, PartialEq)]
 pub struct Card {
     pub id: String,
     pub name: String,
-    pub back_name: Option<String>,
+    pub size: CardSize,
+    /// Any other `<card>` attributes, in document order.
+    pub attributes: Vec<(String, String)>,
+    pub properties: Properties,
+    pub alternates: Vec<CardSide>,
+}
+
+impl Card {
+    pub fn is_player_card(&self) -> bool {
+        self.size == CardSize::Default
+    }
+
+    pub fn is_encounter_card(&self) -> bool {
+        self.size == CardSize::EncounterCard
+    }
+
+    pub fn is_quest_card(&self) -> bool {
+        self.size == CardSize::QuestCard
+    }
+
+    /// The "B" alternate, which OCTGN shows when the card is flipped.
+    pub fn back(&self) -> Option<&CardSide> {
+        self.alternates
+            .iter()
+            .find(|alternate| alternate.alternate_type == "B")
+    }
+
+    /// Whether `side` is the other half of this same printed card (like quest stages 1A/1B) rather
+    /// than a different card printed on the back (like Haldan on the back of Woodman Village).
+    pub fn is_same_card(&self, side: &CardSide) -> bool {
+        match (&self.properties.card_type, &side.properties.card_type) {
+            (Some(front_type), Some(side_type)) => front_type == side_type,
+            _ => true,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -104,12 +530,25 @@ pub struct Set {
     pub id: String,
     pub name: String,
     pub cards: Vec<Card>,
+    pub packs: Vec<Pack>,
+    pub markers: Vec<Marker>,
     pub game: Game,
+    pub game_version: Option<String>,
+    pub version: Option<String>,
+    /// Namespace declarations and any other `<set>` attributes, in document order.
+    pub attributes: Vec<(String, String)>,
+    /// Where the set.xml was loaded from, if it came from disk.
+    pub path: Option<PathBuf>,
 }
 
 impl Set {
     #![allow(clippy::new_ret_no_self)]
-    pub fn new(doc: &Document) -> Result<Set, Box<std::error::Error>> {
+    /// Parse a set made for `game`, the game from the repo's `GameDefinition`.
+    pub fn new(doc: &Document, game: &Game) -> Result<Set, Error> {
+        Set::parse(doc, game).map_err(Error::from)
+    }
+
+    fn parse(doc: &Document, game: &Game) -> Result<Set, Box<dyn std::error::Error>> {
         let node = doc.root().first_child().unwrap();
         let atts = attributes(node.attributes());
         let id = atts
@@ -123,7 +562,37 @@ impl Set {
         let game_id = atts
             .get("gameId")
             .ok_or_else(|| AttributeMissingError::new(node.tag_name().name(), "gameId"))?;
-        let game = Game::from(game_id).ok_or_else(|| NoMatchingGameError::new(game_id))?;
+        if *game_id != game.id() {
+            return Err(NoMatchingGameError::new(game_id).into());
+        }
+        let game = game.clone();
+        let game_version = atts.get("gameVersion").map(|value| value.to_string());
+        let version = atts.get("version").map(|value| value.to_string());
+        let extra_attributes = node
+            .namespaces()
+            .iter()
+            .filter(|namespace| namespace.name() != Some("xml"))
+            .map(|namespace| match namespace.name() {
+                Some(prefix) => (format!("xmlns:{}", prefix), namespace.uri().to_string()),
+                None => ("xmlns".to_string(), namespace.uri().to_string()),
+            })
+            .chain(
+                node.attributes()
+                    .iter()
+                    .filter(|attribute| {
+                        !["id", "name", "gameId", "gameVersion", "version"]
+                            .contains(&attribute.name())
+                    })
+                    .map(|attribute| (attribute.name().to_string(), attribute.value().to_string())),
+            )
+            .collect();
+
+        let packs = child_elements(node, "packaging", "pack")
+            .map(Pack::from_node)
+            .collect::<Result<Vec<Pack>, AttributeMissingError>>()?;
+        let markers = child_elements(node, "markers", "marker")
+            .map(Marker::from_node)
+            .collect::<Result<Vec<Marker>, AttributeMissingError>>()?;
 
         let cards_node = node
             .children()
@@ -131,34 +600,84 @@ impl Set {
             .ok_or_else(|| PropertyMissingError::new("cards"))?;
         let cards = cards_node
             .children()
-            .filter(|card_node| !card_node.attributes().is_empty())
+            .filter(|child| child.is_element() && child.tag_name().name() == "card")
             .map(|card_node| {
-                let atts = attributes(card_node.attributes());
-                let back_name = card_node
+                let required_attribute = |node: roxmltree::Node, attribute| {
+                    node.attribute(attribute)
+                        .map(|value| value.to_string())
+                        .ok_or_else(|| {
+                            Box::<dyn std::error::Error>::from(CardParseError::new(
+                                card_node, node, &name, attribute,
+                            ))
+                        })
+                };
+                let alternates = card_node
                     .children()
-                    .find(|child| child.is_element() && child.tag_name().name() == "alternate")
-                    .map(|alternative_node| {
-                        let atts = attributes(alternative_node.attributes());
-                        atts["name"].to_string()
-                    });
-                Card {
-                    id: atts["id"].to_string(),
-                    name: atts["name"].to_string(),
-                    back_name,
-                }
+                    .filter(|child| child.is_element() && child.tag_name().name() == "alternate")
+                    .map(|alternate_node| {
+                        Ok(CardSide {
+                            alternate_type: required_attribute(alternate_node, "type")?,
+                            name: required_attribute(alternate_node, "name")?,
+                            properties: Properties::from_node(alternate_node),
+                        })
+                    })
+                    .collect::<Result<Vec<CardSide>, Box<dyn std::error::Error>>>()?;
+                let size = card_node
+                    .attribute("size")
+                    .map(CardSize::from)
+                    .unwrap_or_default();
+                let extra_attributes = card_node
+                    .attributes()
+                    .iter()
+                    .filter(|attribute| !["id", "name", "size"].contains(&attribute.name()))
+                    .map(|attribute| (attribute.name().to_string(), attribute.value().to_string()))
+                    .collect();
+
+                Ok(Card {
+                    id: required_attribute(card_node, "id")?,
+                    name: required_attribute(card_node, "name")?,
+                    size,
+                    attributes: extra_attributes,
+                    properties: Properties::from_node(card_node),
+                    alternates,
+                })
             })
-            .collect();
+            .collect::<Result<Vec<Card>, Box<dyn std::error::Error>>>()?;
 
         Ok(Self {
             id,
             name,
             cards,
+            packs,
+            markers,
             game,
+            game_version,
+            version,
+            attributes: extra_attributes,
+            path: None,
         })
     }
 
-    pub fn fetch_all(folder: &std::path::Path) -> Result<Vec<Set>, Box<std::error::Error>> {
-        let sets = WalkDir::new(folder)
+    pub fn fetch_all(folder: &std::path::Path, game: &Game) -> Result<Vec<Set>, Error> {
+        let (sets, mut errors) = Set::fetch_all_tolerant(folder, game);
+
+        if errors.is_empty() {
+            Ok(sets)
+        } else {
+            Err(errors.remove(0).1)
+        }
+    }
+
+    /// Like `fetch_all`, but a file that can't be read or parsed doesn't stop the others from
+    /// loading. Returns every set that parsed, plus each failed file with its error.
+    pub fn fetch_all_tolerant(
+        folder: &std::path::Path,
+        game: &Game,
+    ) -> (Vec<Set>, Vec<SetLoadError>) {
+        let mut sets = vec![];
+        let mut errors = vec![];
+
+        WalkDir::new(folder)
             .into_iter()
             .filter_map(|e| e.ok())
             .filter(|entry| {
@@ -169,27 +688,66 @@ impl Set {
                     Some(extension) => extension == "xml",
                 }
             })
-            .map(|entry| {
-                let path = entry.path();
-                let file = File::open(&path)?;
-                let mut reader = std::io::BufReader::new(file);
-                let mut xml = String::new();
-                reader.read_to_string(&mut xml)?;
-                let doc = Document::parse(&xml)?;
-                Set::new(&doc)
-            })
-            .collect::<Vec<Result<Set, Box<std::error::Error>>>>();
-
-        if sets.iter().any(|result| result.is_err()) {
-            Err(sets
-                .into_iter()
-                .find(|result| result.is_err())
-                .unwrap()
-                .unwrap_err())
-        } else {
-            Ok(sets.into_iter().map(|result| result.unwrap()).collect())
-        }
+            .for_each(|entry| match Set::open(entry.path(), game) {
+                Ok(set) => sets.push(set),
+                Err(err) => errors.push((entry.path().to_path_buf(), err)),
+            });
+
+        (sets, errors)
     }
+
+    fn open(path: &std::path::Path, game: &Game) -> Result<Set, Error> {
+        let file = File::open(&path)?;
+        let mut reader = std::io::BufReader::new(file);
+        let mut xml = String::new();
+        reader.read_to_string(&mut xml)?;
+        let doc = Document::parse(&xml)?;
+        let set = Set::parse(&doc, game).map_err(|err| match err.downcast::<CardParseError>() {
+            Ok(mut err) => {
+                err.path = Some(path.to_path_buf());
+                err
+            }
+            Err(err) => err,
+        });
+        let mut set = set?;
+        set.path = Some(path.to_path_buf());
+
+        Ok(set)
+    }
+}
+
+/// The `item` elements inside the `section` child of `node`, e.g. each `<pack>` in `<packaging>`.
+fn child_elements<'a, 'd: 'a>(
+    node: roxmltree::Node<'a, 'd>,
+    section: &'a str,
+    item: &'a str,
+) -> impl Iterator<Item = roxmltree::Node<'a, 'd>> {
+    node.children()
+        .filter(move |child| child.is_element() && child.tag_name().name() == section)
+        .flat_map(move |section_node| {
+            section_node
+                .children()
+                .filter(move |child| child.is_element() && child.tag_name().name() == item)
+        })
+}
+
+pub(crate) fn required_attribute(
+    node: roxmltree::Node,
+    attribute: &str,
+) -> Result<String, AttributeMissingError> {
+    node.attribute(attribute)
+        .map(|value| value.to_string())
+        .ok_or_else(|| AttributeMissingError::new(node.tag_name().name(), attribute))
+}
+
+/// Split a "Dale. Noble." style value into `["Dale.", "Noble."]`.
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split_terminator('.')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .map(|item| format!("{}.", item))
+        .collect()
 }
 
 fn attributes<'a>(atts: &'a [roxmltree::Attribute]) -> HashMap<&'a str, &'a str> {
@@ -204,16 +762,13 @@ fn attributes<'a>(atts: &'a [roxmltree::Attribute]) -> HashMap<&'a str, &'a str>
 mod tests {
     use super::*;
 
-    use std::{fs::File, io::Read, path::Path};
+    use crate::tests::{downcast_xml, load_octgn_set};
+
+    use std::path::Path;
 
     #[test]
     fn test_new() {
-        let mut file = File::open("fixtures/set.xml").unwrap();
-        let mut xml = String::new();
-        file.read_to_string(&mut xml).unwrap();
-        let doc = Document::parse(&xml).unwrap();
-
-        let set = Set::new(&doc).unwrap();
+        let set = load_octgn_set();
         assert_eq!(&set.name, "The Wilds of Rhovanion");
         assert_eq!(&set.id, "e37145f0-8970-48d3-93bc-cef612226bda");
         assert_eq!(set.game, Game::LOTR);
@@ -230,14 +785,11 @@ mod tests {
  </cards>
 </set>"#;
         let doc = Document::parse(&xml).unwrap();
-        let result = Set::new(&doc);
+        let result = Set::new(&doc, &Game::LOTR);
 
         assert!(result.is_err());
         assert_eq!(
-            *result
-                .unwrap_err()
-                .downcast::<AttributeMissingError>()
-                .unwrap(),
+            *downcast_xml::<AttributeMissingError>(result.unwrap_err()).unwrap(),
             AttributeMissingError::new("set", "id")
         );
     }
@@ -251,14 +803,11 @@ mod tests {
  </cards>
 </set>"#;
         let doc = Document::parse(&xml).unwrap();
-        let result = Set::new(&doc);
+        let result = Set::new(&doc, &Game::LOTR);
 
         assert!(result.is_err());
         assert_eq!(
-            *result
-                .unwrap_err()
-                .downcast::<AttributeMissingError>()
-                .unwrap(),
+            *downcast_xml::<AttributeMissingError>(result.unwrap_err()).unwrap(),
             AttributeMissingError::new("set", "name")
         );
     }
@@ -272,18 +821,39 @@ mod tests {
  </cards>
 </set>"#;
         let doc = Document::parse(&xml).unwrap();
-        let result = Set::new(&doc);
+        let result = Set::new(&doc, &Game::LOTR);
 
         assert!(result.is_err());
         assert_eq!(
-            *result
-                .unwrap_err()
-                .downcast::<AttributeMissingError>()
-                .unwrap(),
+            *downcast_xml::<AttributeMissingError>(result.unwrap_err()).unwrap(),
             AttributeMissingError::new("set", "gameId")
         );
     }
 
+    #[test]
+    fn test_new_other_game() {
+        let xml = r#"
+<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<set name="Other Set" id="e37145f0-8970-48d3-93bc-cef612226bda" gameId="844d5fe3-bdb5-4ad2-ba83-88c2c2db6d88">
+ <cards>
+ </cards>
+</set>"#;
+        let doc = Document::parse(xml).unwrap();
+        let game = Game::Other("844d5fe3-bdb5-4ad2-ba83-88c2c2db6d88".to_string());
+
+        let set = Set::new(&doc, &game).unwrap();
+        assert_eq!(set.game, game);
+
+        let result = Set::new(&doc, &Game::LOTR);
+        match result {
+            Err(err @ Error::WrongGame(_)) => {
+                assert_eq!(err.to_string(), "Made for a different game");
+                assert_eq!(err.exit_code(), 1);
+            }
+            _ => panic!("expected a game mismatch"),
+        }
+    }
+
     #[test]
     fn test_card_back() {
         let xml = r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>
@@ -312,16 +882,225 @@ mod tests {
  </cards>
 </set>"#;
         let doc = Document::parse(&xml).unwrap();
-        let set = Set::new(&doc).unwrap();
+        let set = Set::new(&doc, &Game::LOTR).unwrap();
 
         let card = set.cards.get(0).unwrap();
-        assert!(card.back_name.is_some());
+        assert_eq!(card.alternates.len(), 1);
+
+        let back = card.back().unwrap();
+        assert_eq!(&back.alternate_type, "B");
+        assert_eq!(&back.name, "Haldan");
+        assert_eq!(back.properties.card_type, Some(CardType::ObjectiveAlly));
+        assert_eq!(back.properties.traits, vec!["Woodman.", "Scout."]);
+        assert_eq!(back.properties.willpower, Some(2));
+        assert_eq!(back.properties.attack, Some(3));
+        assert_eq!(back.properties.defense, Some(1));
+        assert_eq!(back.properties.health, Some(4));
+        assert!(!card.is_same_card(back));
+    }
+
+    #[test]
+    fn test_card_properties() {
+        let set = load_octgn_set();
+
+        let brand = &set.cards[0];
+        assert_eq!(&brand.name, "Brand son of Bain");
+        let properties = &brand.properties;
+        assert_eq!(properties.number, Some(1));
+        assert_eq!(properties.quantity, Some(1));
+        assert_eq!(properties.card_type, Some(CardType::Hero));
+        assert_eq!(properties.sphere, Some(Sphere::Leadership));
+        assert_eq!(properties.traits, vec!["Dale.", "Noble."]);
+        assert_eq!(properties.cost, Some(10));
+        assert_eq!(properties.willpower, Some(2));
+        assert_eq!(properties.attack, Some(3));
+        assert_eq!(properties.defense, Some(2));
+        assert_eq!(properties.health, Some(3));
+        assert!(properties
+            .text
+            .as_ref()
+            .unwrap()
+            .starts_with("Each Dale character"));
+        assert!(properties.encounter_set.is_none());
+        assert_eq!(properties.get("Cost"), Some("10"));
+        assert_eq!(properties.raw.len(), 11);
+    }
+
+    #[test]
+    fn test_card_size() {
+        let set = load_octgn_set();
+
+        let sizes = |size: CardSize| set.cards.iter().filter(|card| card.size == size).count();
+        assert_eq!(sizes(CardSize::Default), 15);
+        assert_eq!(sizes(CardSize::EncounterCard), 51);
+        assert_eq!(sizes(CardSize::QuestCard), 13);
+
+        let brand = &set.cards[0];
+        assert!(brand.is_player_card());
+        assert!(brand.attributes.is_empty());
+    }
+
+    #[test]
+    fn test_card_unknown_attributes() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<set name="The Wilds of Rhovanion" id="e37145f0-8970-48d3-93bc-cef612226bda" gameId="a21af4e8-be4b-4cda-a6b6-534f9717391f" gameVersion="2.3.6.0" version="1.0.0">
+ <cards>
+  <card id="1d4d59f4-def5-4c9e-ba3f-8a28e7f66c73" name="Woodman Village" size="LargeCard" alternate="extra">
+    <property name="Type" value="Location"/>
+  </card>
+ </cards>
+</set>"#;
+        let doc = Document::parse(xml).unwrap();
+        let set = Set::new(&doc, &Game::LOTR).unwrap();
+
+        let card = &set.cards[0];
+        assert_eq!(card.size, CardSize::Unknown("LargeCard".to_string()));
+        assert!(!card.is_player_card());
+        assert_eq!(
+            card.attributes,
+            vec![("alternate".to_string(), "extra".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_card_properties_non_numeric() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<set name="The Wilds of Rhovanion" id="e37145f0-8970-48d3-93bc-cef612226bda" gameId="a21af4e8-be4b-4cda-a6b6-534f9717391f" gameVersion="2.3.6.0" version="1.0.0">
+ <cards>
+  <card id="42a5a608-0699-4cd5-b69d-f7c3413cd5cd" name="Traveling North" size="QuestCard">
+    <property name="Type" value="Questing Board"/>
+    <property name="Engagement Cost" value="A"/>
+    <property name="Defense" value="X"/>
+  </card>
+ </cards>
+</set>"#;
+        let doc = Document::parse(xml).unwrap();
+        let set = Set::new(&doc, &Game::LOTR).unwrap();
+
+        let properties = &set.cards[0].properties;
+        assert_eq!(
+            properties.card_type,
+            Some(CardType::Unknown("Questing Board".to_string()))
+        );
+        assert!(properties.engagement_cost.is_none());
+        assert_eq!(properties.get("Engagement Cost"), Some("A"));
+        assert!(properties.defense.is_none());
+        assert_eq!(properties.get("Defense"), Some("X"));
+    }
+
+    #[test]
+    fn test_card_multiple_alternates() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<set name="The Wilds of Rhovanion" id="e37145f0-8970-48d3-93bc-cef612226bda" gameId="a21af4e8-be4b-4cda-a6b6-534f9717391f" gameVersion="2.3.6.0" version="1.0.0">
+ <cards>
+  <card id="b4b0bdc8-5edb-40c9-a9ca-dac60fe7cb38" name="Traveling North" size="QuestCard">
+    <property name="Type" value="Quest"/>
+    <alternate name="Traveling North" type="B">
+      <property name="Type" value="Quest"/>
+    </alternate>
+    <alternate name="Traveling North" type="C"/>
+  </card>
+ </cards>
+</set>"#;
+        let doc = Document::parse(xml).unwrap();
+        let set = Set::new(&doc, &Game::LOTR).unwrap();
+
+        let card = &set.cards[0];
+        assert_eq!(card.alternates.len(), 2);
+        assert_eq!(&card.alternates[1].alternate_type, "C");
+        assert!(card.alternates[1].properties.raw.is_empty());
+
+        let back = card.back().unwrap();
+        assert_eq!(&back.alternate_type, "B");
+        assert!(card.is_same_card(back));
+    }
+
+    #[test]
+    fn test_packaging_and_markers() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<set name="The Wilds of Rhovanion" id="e37145f0-8970-48d3-93bc-cef612226bda" gameId="a21af4e8-be4b-4cda-a6b6-534f9717391f" gameVersion="2.3.6.0" version="1.0.0">
+ <packaging>
+  <pack name="Starter" id="0f4a6e1c-6a7b-4b6e-a2a7-3a0cfd5f1c1e">
+    <pick qty="unlimited" key="Type" value="Hero"/>
+    <options>
+      <option probability="0.25">
+        <pick qty="1" key="Sphere" value="Lore"/>
+      </option>
+    </options>
+    <include id="2b75792d-5873-4fc6-9272-d20dd517d36b" set="e37145f0-8970-48d3-93bc-cef612226bda"/>
+  </pack>
+ </packaging>
+ <markers>
+  <marker id="2f0a5b1e-9a43-4f7b-8f3a-6a2c1d9e0b7a" name="Resource"/>
+  <marker id="8b3f0c6d-1f5e-4c8e-9e7a-5d2b4a6c8e0f" name="Progress"/>
+ </markers>
+ <cards>
+ </cards>
+</set>"#;
+        let doc = Document::parse(xml).unwrap();
+        let set = Set::new(&doc, &Game::LOTR).unwrap();
+
+        assert_eq!(set.markers.len(), 2);
+        assert_eq!(
+            set.markers[1],
+            Marker {
+                id: "8b3f0c6d-1f5e-4c8e-9e7a-5d2b4a6c8e0f".to_string(),
+                name: "Progress".to_string(),
+            }
+        );
+
+        assert_eq!(set.packs.len(), 1);
+        let pack = &set.packs[0];
+        assert_eq!(&pack.name, "Starter");
+        assert_eq!(pack.items.len(), 3);
+        assert_eq!(
+            pack.items[0],
+            PackItem::Pick(Pick {
+                quantity: Quantity::Unlimited,
+                key: Some("Type".to_string()),
+                value: Some("Hero".to_string()),
+                properties: vec![],
+            })
+        );
+        match &pack.items[1] {
+            PackItem::Options(options) => {
+                assert_eq!(options.len(), 1);
+                assert_eq!(options[0].probability, 0.25);
+                assert_eq!(options[0].items.len(), 1);
+            }
+            item => panic!("expected options, got {:?}", item),
+        }
+        match &pack.items[2] {
+            PackItem::Include(include) => {
+                assert_eq!(&include.id, "2b75792d-5873-4fc6-9272-d20dd517d36b")
+            }
+            item => panic!("expected include, got {:?}", item),
+        }
+    }
+
+    #[test]
+    fn test_marker_missing_name() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<set name="The Wilds of Rhovanion" id="e37145f0-8970-48d3-93bc-cef612226bda" gameId="a21af4e8-be4b-4cda-a6b6-534f9717391f">
+ <markers>
+  <marker id="2f0a5b1e-9a43-4f7b-8f3a-6a2c1d9e0b7a"/>
+ </markers>
+ <cards>
+ </cards>
+</set>"#;
+        let doc = Document::parse(xml).unwrap();
+        let result = Set::new(&doc, &Game::LOTR);
+
+        assert_eq!(
+            *downcast_xml::<AttributeMissingError>(result.unwrap_err()).unwrap(),
+            AttributeMissingError::new("marker", "name")
+        );
     }
 
     #[test]
     fn test_fetch_all() {
         let dir = Path::new("fixtures/octgn/o8g/Sets");
-        let result = Set::fetch_all(&dir);
+        let result = Set::fetch_all(&dir, &Game::LOTR);
         assert!(result.is_ok());
 
         let sets = result.unwrap();
@@ -331,7 +1110,95 @@ mod tests {
     #[test]
     fn test_fetch_all_err() {
         let dir = Path::new("fixtures/octgn");
-        let result = Set::fetch_all(&dir);
+        let result = Set::fetch_all(&dir, &Game::LOTR);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_fetch_all_tolerant() {
+        let tmp_dir = tempdir::TempDir::new("sets").unwrap();
+        std::fs::copy("fixtures/set.xml", tmp_dir.path().join("set.xml")).unwrap();
+        let bad_path = tmp_dir.path().join("bad.xml");
+        std::fs::write(&bad_path, "<set><cards>").unwrap();
+
+        let (sets, errors) = Set::fetch_all_tolerant(tmp_dir.path(), &Game::LOTR);
+        assert_eq!(sets.len(), 1);
+        assert_eq!(&sets[0].name, "The Wilds of Rhovanion");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, bad_path);
+
+        assert!(Set::fetch_all(tmp_dir.path(), &Game::LOTR).is_err());
+
+        // sets for another game are errors too
+        let other_game = Game::Other("844d5fe3-bdb5-4ad2-ba83-88c2c2db6d88".to_string());
+        let (sets, errors) = Set::fetch_all_tolerant(tmp_dir.path(), &other_game);
+        assert!(sets.is_empty());
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn test_card_missing_id() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<set name="The Wilds of Rhovanion" id="e37145f0-8970-48d3-93bc-cef612226bda" gameId="a21af4e8-be4b-4cda-a6b6-534f9717391f">
+ <cards>
+  <card name="Brand son of Bain">
+    <property name="Card Number" value="1"/>
+  </card>
+ </cards>
+</set>"#;
+        let doc = Document::parse(xml).unwrap();
+        let err = downcast_xml::<CardParseError>(Set::new(&doc, &Game::LOTR).unwrap_err()).unwrap();
+
+        assert_eq!(
+            *err,
+            CardParseError {
+                path: None,
+                line: 4,
+                column: 3,
+                set_name: "The Wilds of Rhovanion".to_string(),
+                card_id: None,
+                card_name: Some("Brand son of Bain".to_string()),
+                error: AttributeMissingError::new("card", "id"),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "4:3: <card> is missing attribute 'id'. (card 'Brand son of Bain' in Set 'The Wilds of Rhovanion')"
+        );
+    }
+
+    #[test]
+    fn test_alternate_missing_name() {
+        let tmp_dir = tempdir::TempDir::new("sets").unwrap();
+        let path = tmp_dir.path().join("set.xml");
+        std::fs::write(
+            &path,
+            r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<set name="The Wilds of Rhovanion" id="e37145f0-8970-48d3-93bc-cef612226bda" gameId="a21af4e8-be4b-4cda-a6b6-534f9717391f">
+ <cards>
+  <card id="1d4d59f4-def5-4c9e-ba3f-8a28e7f66c73" name="Woodman Village">
+    <alternate type="B"/>
+  </card>
+ </cards>
+</set>"#,
+        )
+        .unwrap();
+
+        let (sets, errors) = Set::fetch_all_tolerant(tmp_dir.path(), &Game::LOTR);
+        assert!(sets.is_empty());
+        let err = match &errors[0].1 {
+            Error::Xml(err) => err.downcast_ref::<CardParseError>().unwrap(),
+            err => panic!("unexpected error: {}", err),
+        };
+        assert_eq!(err.path.as_ref(), Some(&path));
+        assert_eq!((err.line, err.column), (5, 5));
+        assert_eq!(
+            err.card_id.as_ref().unwrap(),
+            "1d4d59f4-def5-4c9e-ba3f-8a28e7f66c73"
+        );
+        assert_eq!(err.error, AttributeMissingError::new("alternate", "name"));
+        assert!(err
+            .to_string()
+            .starts_with(&format!("{}:5:5: ", path.display())));
+    }
 }
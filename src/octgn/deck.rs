@@ -0,0 +1,447 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use roxmltree::Document;
+
+use crate::Error;
+
+use super::{AttributeMissingError, Card, CardType, Game, NoMatchingGameError, Set};
+
+/// The sections the OCTGN client creates for a new LotR deck, and whether each one is shared.
+const SECTIONS: [(&str, bool); 9] = [
+    ("Hero", false),
+    ("Ally", false),
+    ("Attachment", false),
+    ("Event", false),
+    ("Side Quest", false),
+    ("Sideboard", false),
+    ("Quest", true),
+    ("Encounter", true),
+    ("Special", true),
+];
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct CardsNotFoundError {
+    cards: Vec<String>,
+}
+
+impl std::error::Error for CardsNotFoundError {}
+
+impl fmt::Display for CardsNotFoundError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not find cards: '{}'.", self.cards.join("', '"))
+    }
+}
+
+impl CardsNotFoundError {
+    pub fn new(cards: Vec<String>) -> Self {
+        Self { cards }
+    }
+}
+
+/// A card entry in a deck section: `<card qty="3" id="...">Name</card>`.
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct DeckCard {
+    pub id: String,
+    pub name: String,
+    pub quantity: u32,
+}
+
+/// A `<section>` of a deck, e.g. Hero, Ally or Encounter.
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct Section {
+    pub name: String,
+    /// Shared sections like Quest and Encounter belong to the table rather than a player.
+    pub shared: bool,
+    pub cards: Vec<DeckCard>,
+}
+
+/// An OCTGN `.o8d` deck.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Deck {
+    pub game: Game,
+    pub sections: Vec<Section>,
+    pub notes: Option<String>,
+}
+
+/// A deck entry matched to the card it refers to.
+#[derive(Debug)]
+pub struct ResolvedCard<'a> {
+    pub section: &'a Section,
+    pub entry: &'a DeckCard,
+    pub set: &'a Set,
+    pub card: &'a Card,
+}
+
+#[derive(Debug)]
+pub struct ResolvedDeck<'a> {
+    pub cards: Vec<ResolvedCard<'a>>,
+    /// Entries whose GUID isn't in any of the loaded sets.
+    pub unresolved: Vec<(&'a Section, &'a DeckCard)>,
+}
+
+impl Deck {
+    #![allow(clippy::new_ret_no_self)]
+    /// Parse a deck made for `game`, the game from the repo's `GameDefinition`.
+    pub fn new(doc: &Document, game: &Game) -> Result<Deck, Error> {
+        Deck::parse(doc, game).map_err(Error::from)
+    }
+
+    fn parse(doc: &Document, game: &Game) -> Result<Deck, Box<dyn std::error::Error>> {
+        let node = doc.root_element();
+        let game_id = node
+            .attribute("game")
+            .ok_or_else(|| AttributeMissingError::new(node.tag_name().name(), "game"))?;
+        if game_id != game.id() {
+            return Err(NoMatchingGameError::new(game_id).into());
+        }
+        let game = game.clone();
+
+        let sections = node
+            .children()
+            .filter(|child| child.is_element() && child.tag_name().name() == "section")
+            .map(|section_node| {
+                let name = section_node
+                    .attribute("name")
+                    .ok_or_else(|| AttributeMissingError::new("section", "name"))?;
+                let shared = section_node
+                    .attribute("shared")
+                    .map(|shared| shared.eq_ignore_ascii_case("true"))
+                    .unwrap_or(false);
+                let cards = section_node
+                    .children()
+                    .filter(|child| child.is_element() && child.tag_name().name() == "card")
+                    .map(|card_node| {
+                        let id = card_node
+                            .attribute("id")
+                            .ok_or_else(|| AttributeMissingError::new("card", "id"))?;
+                        let quantity = card_node
+                            .attribute("qty")
+                            .and_then(|qty| qty.parse().ok())
+                            .ok_or_else(|| AttributeMissingError::new("card", "qty"))?;
+
+                        Ok(DeckCard {
+                            id: id.to_string(),
+                            name: card_node.text().unwrap_or("").trim().to_string(),
+                            quantity,
+                        })
+                    })
+                    .collect::<Result<Vec<DeckCard>, AttributeMissingError>>()?;
+
+                Ok(Section {
+                    name: name.to_string(),
+                    shared,
+                    cards,
+                })
+            })
+            .collect::<Result<Vec<Section>, AttributeMissingError>>()?;
+
+        let notes = node
+            .children()
+            .find(|child| child.is_element() && child.tag_name().name() == "notes")
+            .and_then(|notes_node| notes_node.text().map(|notes| notes.to_string()))
+            .filter(|notes| !notes.is_empty());
+
+        Ok(Deck {
+            game,
+            sections,
+            notes,
+        })
+    }
+
+    pub fn open(path: &Path, game: &Game) -> Result<Deck, Error> {
+        let mut file = File::open(path)?;
+        let mut xml = String::new();
+        file.read_to_string(&mut xml)?;
+        let doc = Document::parse(&xml)?;
+
+        Deck::new(&doc, game)
+    }
+
+    /// Build a deck for `game` from `(name or GUID, quantity)` pairs, placing each card in the
+    /// section for its Type. Names that appear in more than one set use the first set they're
+    /// found in.
+    pub fn from_cards(
+        cards: &[(String, u32)],
+        sets: &[Set],
+        game: &Game,
+    ) -> Result<Deck, CardsNotFoundError> {
+        let mut sections = SECTIONS
+            .iter()
+            .map(|(name, shared)| Section {
+                name: name.to_string(),
+                shared: *shared,
+                cards: vec![],
+            })
+            .collect::<Vec<Section>>();
+        let mut not_found = vec![];
+
+        for (name_or_id, quantity) in cards {
+            let card = sets
+                .iter()
+                .flat_map(|set| set.cards.iter())
+                .find(|card| &card.id == name_or_id)
+                .or_else(|| {
+                    sets.iter()
+                        .flat_map(|set| set.cards.iter())
+                        .find(|card| &card.name == name_or_id)
+                });
+            let card = match card {
+                Some(card) => card,
+                None => {
+                    not_found.push(name_or_id.to_string());
+                    continue;
+                }
+            };
+
+            let section_name = section_for(card.properties.card_type.as_ref());
+            let section = sections
+                .iter_mut()
+                .find(|section| section.name == section_name)
+                .unwrap();
+            match section.cards.iter_mut().find(|entry| entry.id == card.id) {
+                Some(entry) => entry.quantity += quantity,
+                None => section.cards.push(DeckCard {
+                    id: card.id.to_string(),
+                    name: card.name.to_string(),
+                    quantity: *quantity,
+                }),
+            }
+        }
+
+        if !not_found.is_empty() {
+            return Err(CardsNotFoundError::new(not_found));
+        }
+
+        Ok(Deck {
+            game: game.clone(),
+            sections,
+            notes: None,
+        })
+    }
+
+    /// Find a section by name, e.g. "Hero".
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    /// Match every entry to a card from `sets` by GUID.
+    pub fn resolve<'a>(&'a self, sets: &'a [Set]) -> ResolvedDeck<'a> {
+        let cards_by_id = sets
+            .iter()
+            .flat_map(|set| {
+                set.cards
+                    .iter()
+                    .map(move |card| (card.id.as_str(), (set, card)))
+            })
+            .collect::<HashMap<&str, (&Set, &Card)>>();
+
+        let mut resolved = ResolvedDeck {
+            cards: vec![],
+            unresolved: vec![],
+        };
+        for section in &self.sections {
+            for entry in &section.cards {
+                match cards_by_id.get(entry.id.as_str()) {
+                    Some((set, card)) => resolved.cards.push(ResolvedCard {
+                        section,
+                        entry,
+                        set,
+                        card,
+                    }),
+                    None => resolved.unresolved.push((section, entry)),
+                }
+            }
+        }
+
+        resolved
+    }
+}
+
+/// The deck section OCTGN expects a card of this type to be in.
+fn section_for(card_type: Option<&CardType>) -> &'static str {
+    match card_type {
+        Some(CardType::Hero) => "Hero",
+        Some(CardType::Ally) => "Ally",
+        Some(CardType::Attachment) => "Attachment",
+        Some(CardType::Event) => "Event",
+        Some(CardType::PlayerSideQuest) => "Side Quest",
+        Some(CardType::Quest) => "Quest",
+        Some(CardType::Contract)
+        | Some(CardType::Treasure)
+        | Some(CardType::Campaign)
+        | Some(CardType::Boon)
+        | Some(CardType::Burden) => "Special",
+        _ => "Encounter",
+    }
+}
+
+/// Parse a command line card like "3x Redwater Sentry" into its name and quantity, defaulting to
+/// a single copy.
+pub fn parse_card_quantity(card: &str) -> (String, u32) {
+    let mut parts = card.trim().splitn(2, ' ');
+    let first = parts.next().unwrap_or("");
+    let quantity = first.trim_end_matches(['x', 'X']).parse::<u32>().ok();
+
+    match (quantity, parts.next()) {
+        (Some(quantity), Some(name)) => (name.trim().to_string(), quantity),
+        _ => (card.trim().to_string(), 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tests::{downcast_xml, load_octgn_set};
+
+    #[test]
+    fn test_open() {
+        let deck = Deck::open(Path::new("fixtures/deck.o8d"), &Game::LOTR).unwrap();
+        assert_eq!(deck.game, Game::LOTR);
+        assert_eq!(deck.sections.len(), 7);
+        assert_eq!(deck.notes.as_ref().unwrap(), "Dale starter");
+
+        let heroes = deck.section("Hero").unwrap();
+        assert!(!heroes.shared);
+        assert_eq!(
+            heroes.cards[0],
+            DeckCard {
+                id: "2b75792d-5873-4fc6-9272-d20dd517d36b".to_string(),
+                name: "Brand son of Bain".to_string(),
+                quantity: 1,
+            }
+        );
+
+        let allies = deck.section("Ally").unwrap();
+        assert_eq!(allies.cards[0].quantity, 3);
+        assert!(deck.section("Event").unwrap().cards.is_empty());
+        assert!(deck.section("Quest").unwrap().shared);
+    }
+
+    #[test]
+    fn test_new_missing_game() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<deck>
+  <section name="Hero" shared="False" />
+</deck>"#;
+        let doc = Document::parse(xml).unwrap();
+        let result = Deck::new(&doc, &Game::LOTR);
+
+        assert_eq!(
+            *downcast_xml::<AttributeMissingError>(result.unwrap_err()).unwrap(),
+            AttributeMissingError::new("deck", "game")
+        );
+    }
+
+    #[test]
+    fn test_new_wrong_game() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<deck game="844d5fe3-bdb5-4ad2-ba83-88c2c2db6d88">
+  <section name="Hero" shared="False" />
+</deck>"#;
+        let doc = Document::parse(xml).unwrap();
+        let result = Deck::new(&doc, &Game::LOTR);
+
+        match result {
+            Err(err @ Error::WrongGame(_)) => {
+                assert_eq!(err.to_string(), "Made for a different game");
+                assert_eq!(err.exit_code(), 1);
+            }
+            _ => panic!("expected a game mismatch"),
+        }
+
+        let game = Game::Other("844d5fe3-bdb5-4ad2-ba83-88c2c2db6d88".to_string());
+        assert_eq!(Deck::new(&doc, &game).unwrap().game, game);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let sets = vec![load_octgn_set()];
+        let deck = Deck::open(Path::new("fixtures/deck.o8d"), &Game::LOTR).unwrap();
+
+        let resolved = deck.resolve(&sets);
+        assert_eq!(resolved.cards.len(), 6);
+        assert_eq!(&resolved.cards[0].card.name, "Brand son of Bain");
+        assert_eq!(&resolved.cards[0].set.name, "The Wilds of Rhovanion");
+        assert_eq!(&resolved.cards[0].section.name, "Hero");
+
+        assert_eq!(resolved.unresolved.len(), 1);
+        let (section, entry) = resolved.unresolved[0];
+        assert_eq!(&section.name, "Ally");
+        assert_eq!(&entry.id, "00000000-0000-0000-0000-000000000000");
+    }
+
+    #[test]
+    fn test_from_cards() {
+        let sets = vec![load_octgn_set()];
+        let cards = vec![
+            ("Brand son of Bain".to_string(), 1),
+            ("b69ae054-e422-43c7-a9b3-42f334ab61a5".to_string(), 2),
+            ("Redwater Sentry".to_string(), 1),
+            ("Traveling North".to_string(), 1),
+            ("Woodman Village".to_string(), 1),
+        ];
+
+        let deck = Deck::from_cards(&cards, &sets, &Game::LOTR).unwrap();
+        assert_eq!(deck.game, Game::LOTR);
+        assert_eq!(deck.sections.len(), 9);
+        assert_eq!(
+            &deck.section("Hero").unwrap().cards[0].name,
+            "Brand son of Bain"
+        );
+        assert_eq!(
+            deck.section("Ally").unwrap().cards,
+            vec![DeckCard {
+                id: "b69ae054-e422-43c7-a9b3-42f334ab61a5".to_string(),
+                name: "Redwater Sentry".to_string(),
+                quantity: 3,
+            }]
+        );
+        assert_eq!(deck.section("Quest").unwrap().cards.len(), 1);
+        assert_eq!(deck.section("Encounter").unwrap().cards.len(), 1);
+        assert!(deck.section("Event").unwrap().cards.is_empty());
+
+        let game = Game::Other("844d5fe3-bdb5-4ad2-ba83-88c2c2db6d88".to_string());
+        let deck = Deck::from_cards(&cards, &sets, &game).unwrap();
+        assert_eq!(deck.game, game);
+    }
+
+    #[test]
+    fn test_from_cards_not_found() {
+        let sets = vec![load_octgn_set()];
+        let cards = vec![
+            ("Brand son of Bain".to_string(), 1),
+            ("Gandalf".to_string(), 1),
+        ];
+
+        let result = Deck::from_cards(&cards, &sets, &Game::LOTR);
+        assert_eq!(
+            result.unwrap_err(),
+            CardsNotFoundError::new(vec!["Gandalf".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_parse_card_quantity() {
+        assert_eq!(
+            parse_card_quantity("3x Redwater Sentry"),
+            ("Redwater Sentry".to_string(), 3)
+        );
+        assert_eq!(
+            parse_card_quantity("2 Redwater Sentry"),
+            ("Redwater Sentry".to_string(), 2)
+        );
+        assert_eq!(
+            parse_card_quantity("Brand son of Bain"),
+            ("Brand son of Bain".to_string(), 1)
+        );
+        assert_eq!(
+            parse_card_quantity("b69ae054-e422-43c7-a9b3-42f334ab61a5"),
+            ("b69ae054-e422-43c7-a9b3-42f334ab61a5".to_string(), 1)
+        );
+    }
+}
@@ -0,0 +1,427 @@
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use roxmltree::{Document, Node};
+
+use crate::Error;
+
+use super::{
+    required_attribute, AttributeMissingError, Card, CardSize, Game, InvalidAttributeError,
+    PropertyMissingError, Set, LOTR_ID,
+};
+
+/// The dimensions and images of a card size, from the game's `<card>` element or a `<size>`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SizeDefinition {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub front: Option<String>,
+    pub back: Option<String>,
+    pub corner_radius: Option<u32>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PropertyType {
+    Integer,
+    String,
+    RichText,
+    Unknown(String),
+}
+
+impl From<&str> for PropertyType {
+    fn from(s: &str) -> Self {
+        match s {
+            "Integer" => PropertyType::Integer,
+            "String" => PropertyType::String,
+            "RichText" => PropertyType::RichText,
+            other => PropertyType::Unknown(other.to_string()),
+        }
+    }
+}
+
+/// A card `<property>` declared by the game.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PropertyDefinition {
+    pub name: String,
+    pub property_type: PropertyType,
+    pub hidden: bool,
+}
+
+/// The `o8g/definition.xml` describing the game sets are made for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameDefinition {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    /// The size used by cards without a `size` attribute.
+    pub card_size: SizeDefinition,
+    pub sizes: Vec<SizeDefinition>,
+    pub properties: Vec<PropertyDefinition>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum CardDefinitionErrorKind {
+    UndeclaredProperty(String),
+    NotAnInteger { property: String, value: String },
+    UndeclaredSize(String),
+}
+
+/// A card that doesn't conform to the game definition.
+#[derive(Debug, Eq, PartialEq)]
+pub struct CardDefinitionError {
+    pub card_id: String,
+    pub card_name: String,
+    pub kind: CardDefinitionErrorKind,
+}
+
+impl std::error::Error for CardDefinitionError {}
+
+impl fmt::Display for CardDefinitionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Card '{}' ({}) ", self.card_name, self.card_id)?;
+        match &self.kind {
+            CardDefinitionErrorKind::UndeclaredProperty(property) => {
+                write!(f, "has undeclared property '{}'.", property)
+            }
+            CardDefinitionErrorKind::NotAnInteger { property, value } => write!(
+                f,
+                "has non-integer value '{}' for Integer property '{}'.",
+                value, property
+            ),
+            CardDefinitionErrorKind::UndeclaredSize(size) => {
+                write!(f, "has undeclared size '{}'.", size)
+            }
+        }
+    }
+}
+
+impl CardDefinitionError {
+    fn new(card: &Card, kind: CardDefinitionErrorKind) -> Self {
+        Self {
+            card_id: card.id.to_string(),
+            card_name: card.name.to_string(),
+            kind,
+        }
+    }
+}
+
+impl GameDefinition {
+    #![allow(clippy::new_ret_no_self)]
+    pub fn new(doc: &Document) -> Result<GameDefinition, Error> {
+        GameDefinition::parse(doc).map_err(Error::Xml)
+    }
+
+    fn parse(doc: &Document) -> Result<GameDefinition, Box<dyn std::error::Error>> {
+        let node = doc.root_element();
+        let card_node = node
+            .children()
+            .find(|child| child.is_element() && child.tag_name().name() == "card")
+            .ok_or_else(|| PropertyMissingError::new("card"))?;
+
+        let properties = card_node
+            .children()
+            .filter(|child| child.is_element() && child.tag_name().name() == "property")
+            .map(|property_node| {
+                Ok(PropertyDefinition {
+                    name: required_attribute(property_node, "name")?,
+                    property_type: property_node
+                        .attribute("type")
+                        .map(PropertyType::from)
+                        .unwrap_or(PropertyType::String),
+                    hidden: property_node
+                        .attribute("hidden")
+                        .map(|hidden| hidden.eq_ignore_ascii_case("true"))
+                        .unwrap_or(false),
+                })
+            })
+            .collect::<Result<Vec<PropertyDefinition>, AttributeMissingError>>()?;
+        // older definitions wrap the sizes in a <sizes> element
+        let sizes = card_node
+            .descendants()
+            .filter(|child| child.is_element() && child.tag_name().name() == "size")
+            .map(|size_node| size(size_node, required_attribute(size_node, "name")?))
+            .collect::<Result<Vec<SizeDefinition>, Box<dyn std::error::Error>>>()?;
+
+        Ok(GameDefinition {
+            id: required_attribute(node, "id")?,
+            name: required_attribute(node, "name")?,
+            version: required_attribute(node, "version")?,
+            card_size: size(card_node, CardSize::Default.as_str().to_string())?,
+            sizes,
+            properties,
+        })
+    }
+
+    pub fn open(path: &Path) -> Result<GameDefinition, Error> {
+        let mut xml = String::new();
+        File::open(path)?.read_to_string(&mut xml)?;
+        let doc = Document::parse(&xml)?;
+
+        GameDefinition::new(&doc)
+    }
+
+    pub fn game(&self) -> Game {
+        if self.id == LOTR_ID {
+            Game::LOTR
+        } else {
+            Game::Other(self.id.to_string())
+        }
+    }
+
+    pub fn property(&self, name: &str) -> Option<&PropertyDefinition> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+    }
+
+    pub fn size(&self, size: &CardSize) -> Option<&SizeDefinition> {
+        match size {
+            CardSize::Default => Some(&self.card_size),
+            _ => self
+                .sizes
+                .iter()
+                .find(|definition| definition.name == size.as_str()),
+        }
+    }
+
+    /// Check every card and alternate in `set` only uses declared sizes and properties, and that
+    /// Integer properties hold integers.
+    pub fn validate(&self, set: &Set) -> Vec<CardDefinitionError> {
+        let mut errors = vec![];
+
+        for card in &set.cards {
+            if self.size(&card.size).is_none() {
+                errors.push(CardDefinitionError::new(
+                    card,
+                    CardDefinitionErrorKind::UndeclaredSize(card.size.to_string()),
+                ));
+            }
+
+            let raw_properties = card
+                .properties
+                .raw
+                .iter()
+                .chain(card.alternates.iter().flat_map(|side| &side.properties.raw));
+            for (name, value) in raw_properties {
+                match self.property(name) {
+                    None => errors.push(CardDefinitionError::new(
+                        card,
+                        CardDefinitionErrorKind::UndeclaredProperty(name.to_string()),
+                    )),
+                    Some(property) => {
+                        if property.property_type == PropertyType::Integer
+                            && !value.is_empty()
+                            && value.parse::<i64>().is_err()
+                        {
+                            errors.push(CardDefinitionError::new(
+                                card,
+                                CardDefinitionErrorKind::NotAnInteger {
+                                    property: name.to_string(),
+                                    value: value.to_string(),
+                                },
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        errors
+    }
+}
+
+fn size(node: Node, name: String) -> Result<SizeDefinition, Box<dyn std::error::Error>> {
+    let dimension = |attribute| -> Result<u32, Box<dyn std::error::Error>> {
+        let value = required_attribute(node, attribute)?;
+        value.parse().map_err(|_| {
+            InvalidAttributeError::new(node.tag_name().name(), attribute, &value).into()
+        })
+    };
+
+    Ok(SizeDefinition {
+        name,
+        width: dimension("width")?,
+        height: dimension("height")?,
+        front: node.attribute("front").map(|front| front.to_string()),
+        back: node.attribute("back").map(|back| back.to_string()),
+        corner_radius: node
+            .attribute("cornerRadius")
+            .and_then(|radius| radius.parse().ok()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::{CardSide, Properties};
+    use crate::tests::downcast_xml;
+
+    fn definition() -> GameDefinition {
+        GameDefinition::open(Path::new("fixtures/definition.xml")).unwrap()
+    }
+
+    fn card(size: CardSize, properties: Vec<(&str, &str)>) -> Card {
+        Card {
+            id: "2b75792d-5873-4fc6-9272-d20dd517d36b".to_string(),
+            name: "Brand son of Bain".to_string(),
+            size,
+            attributes: vec![],
+            properties: Properties::from_raw(
+                properties
+                    .into_iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect(),
+            ),
+            alternates: vec![],
+        }
+    }
+
+    fn set(cards: Vec<Card>) -> Set {
+        Set {
+            id: "e37145f0-8970-48d3-93bc-cef612226bda".to_string(),
+            name: "The Wilds of Rhovanion".to_string(),
+            cards,
+            packs: vec![],
+            markers: vec![],
+            game: Game::LOTR,
+            game_version: None,
+            version: None,
+            attributes: vec![],
+            path: None,
+        }
+    }
+
+    #[test]
+    fn test_open() {
+        let definition = definition();
+        assert_eq!(&definition.id, LOTR_ID);
+        assert_eq!(&definition.name, "The Lord of the Rings: The Card Game");
+        assert_eq!(&definition.version, "2.3.6.0");
+        assert_eq!(definition.game(), Game::LOTR);
+        assert_eq!(definition.properties.len(), 18);
+        assert_eq!(
+            definition.property("Card Number"),
+            Some(&PropertyDefinition {
+                name: "Card Number".to_string(),
+                property_type: PropertyType::Integer,
+                hidden: true,
+            })
+        );
+        assert_eq!(
+            definition.property("Text").unwrap().property_type,
+            PropertyType::RichText
+        );
+    }
+
+    #[test]
+    fn test_sizes() {
+        let definition = definition();
+        assert_eq!(definition.card_size.width, 63);
+        assert_eq!(definition.card_size.height, 88);
+        assert_eq!(
+            definition.size(&CardSize::Default),
+            Some(&definition.card_size)
+        );
+
+        let quest = definition.size(&CardSize::QuestCard).unwrap();
+        assert_eq!(quest.width, 88);
+        assert_eq!(quest.height, 63);
+        assert_eq!(quest.back.as_ref().unwrap(), "cards/quest.jpg");
+        assert!(definition
+            .size(&CardSize::Unknown("LargeCard".to_string()))
+            .is_none());
+    }
+
+    #[test]
+    fn test_other_game() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<game name="Other" id="844d5fe3-bdb5-4ad2-ba83-88c2c2db6d88" version="1.0.0.0">
+  <card back="back.jpg" front="front.jpg" width="50" height="70" />
+</game>"#;
+        let doc = Document::parse(xml).unwrap();
+        let definition = GameDefinition::new(&doc).unwrap();
+
+        assert_eq!(
+            definition.game(),
+            Game::Other("844d5fe3-bdb5-4ad2-ba83-88c2c2db6d88".to_string())
+        );
+        assert!(definition.properties.is_empty());
+        assert!(definition.sizes.is_empty());
+    }
+
+    #[test]
+    fn test_missing_version() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<game name="Other" id="844d5fe3-bdb5-4ad2-ba83-88c2c2db6d88">
+  <card back="back.jpg" front="front.jpg" width="50" height="70" />
+</game>"#;
+        let doc = Document::parse(xml).unwrap();
+        let result = GameDefinition::new(&doc);
+
+        assert_eq!(
+            *downcast_xml::<AttributeMissingError>(result.unwrap_err()).unwrap(),
+            AttributeMissingError::new("game", "version")
+        );
+    }
+
+    #[test]
+    fn test_invalid_size() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<game name="Other" id="844d5fe3-bdb5-4ad2-ba83-88c2c2db6d88" version="1.0.0.0">
+  <card back="back.jpg" front="front.jpg" width="50" height="70">
+    <size name="QuestCard" width="wide" height="50" />
+  </card>
+</game>"#;
+        let doc = Document::parse(xml).unwrap();
+        let result = GameDefinition::new(&doc);
+
+        let err = downcast_xml::<InvalidAttributeError>(result.unwrap_err()).unwrap();
+        assert_eq!(*err, InvalidAttributeError::new("size", "width", "wide"));
+        assert_eq!(err.to_string(), "<size> has an invalid 'width': 'wide'.");
+    }
+
+    #[test]
+    fn test_validate() {
+        let definition = definition();
+        let mut back = card(CardSize::Default, vec![("Bogus", "1")]);
+        back.name = "Back".to_string();
+        let mut alternate = card(
+            CardSize::QuestCard,
+            vec![("Card Number", "1"), ("Quantity", "X"), ("Cost", "X")],
+        );
+        alternate.alternates.push(CardSide {
+            alternate_type: "B".to_string(),
+            name: back.name,
+            properties: back.properties,
+        });
+        let set = set(vec![
+            card(
+                CardSize::Default,
+                vec![("Card Number", "1"), ("Cost", "10")],
+            ),
+            card(CardSize::Unknown("LargeCard".to_string()), vec![]),
+            alternate,
+        ]);
+
+        let errors = definition.validate(&set);
+        assert_eq!(
+            errors.iter().map(|error| &error.kind).collect::<Vec<_>>(),
+            vec![
+                &CardDefinitionErrorKind::UndeclaredSize("LargeCard".to_string()),
+                &CardDefinitionErrorKind::NotAnInteger {
+                    property: "Quantity".to_string(),
+                    value: "X".to_string(),
+                },
+                &CardDefinitionErrorKind::UndeclaredProperty("Bogus".to_string()),
+            ]
+        );
+        assert_eq!(
+            errors[2].to_string(),
+            "Card 'Brand son of Bain' (2b75792d-5873-4fc6-9272-d20dd517d36b) has undeclared property 'Bogus'."
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt;
+
+use roxmltree::Node;
+
+use super::{required_attribute, AttributeMissingError};
+
+/// A `<marker>` from a set's `<markers>` section.
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct Marker {
+    pub id: String,
+    pub name: String,
+}
+
+/// A `<pack>` from a set's `<packaging>` section, describing how OCTGN builds a booster.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pack {
+    pub id: String,
+    pub name: String,
+    pub items: Vec<PackItem>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PackItem {
+    Pick(Pick),
+    Options(Vec<PackOption>),
+    Include(Include),
+}
+
+/// How many cards a `<pick>` takes.
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub enum Quantity {
+    Count(u32),
+    Unlimited,
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Quantity::Count(count) => write!(f, "{}", count),
+            Quantity::Unlimited => write!(f, "unlimited"),
+        }
+    }
+}
+
+/// Pick `quantity` cards matching either the `key`/`value` attributes or the nested properties.
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct Pick {
+    pub quantity: Quantity,
+    pub key: Option<String>,
+    pub value: Option<String>,
+    pub properties: Vec<(String, String)>,
+}
+
+/// One `<option>` of an `<options>` block, chosen with the given probability.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackOption {
+    pub probability: f64,
+    pub items: Vec<PackItem>,
+}
+
+/// A specific card, possibly from another set, that is always added to the pack.
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct Include {
+    pub id: String,
+    pub set: String,
+    pub properties: Vec<(String, String)>,
+}
+
+impl Marker {
+    pub(super) fn from_node(node: Node) -> Result<Marker, AttributeMissingError> {
+        Ok(Marker {
+            id: required_attribute(node, "id")?,
+            name: required_attribute(node, "name")?,
+        })
+    }
+}
+
+impl Pack {
+    pub(super) fn from_node(node: Node) -> Result<Pack, AttributeMissingError> {
+        Ok(Pack {
+            id: required_attribute(node, "id")?,
+            name: required_attribute(node, "name")?,
+            items: pack_items(node)?,
+        })
+    }
+}
+
+fn pack_items(node: Node) -> Result<Vec<PackItem>, AttributeMissingError> {
+    node.children()
+        .filter(|child| child.is_element())
+        .filter_map(|child| match child.tag_name().name() {
+            "pick" => Some(pick(child).map(PackItem::Pick)),
+            "options" => Some(options(child).map(PackItem::Options)),
+            "include" => Some(include(child).map(PackItem::Include)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn pick(node: Node) -> Result<Pick, AttributeMissingError> {
+    let qty = required_attribute(node, "qty")?;
+    let quantity = if qty == "unlimited" {
+        Quantity::Unlimited
+    } else {
+        Quantity::Count(
+            qty.parse()
+                .map_err(|_| AttributeMissingError::new("pick", "qty"))?,
+        )
+    };
+
+    Ok(Pick {
+        quantity,
+        key: node.attribute("key").map(|key| key.to_string()),
+        value: node.attribute("value").map(|value| value.to_string()),
+        properties: properties(node),
+    })
+}
+
+fn options(node: Node) -> Result<Vec<PackOption>, AttributeMissingError> {
+    node.children()
+        .filter(|child| child.is_element() && child.tag_name().name() == "option")
+        .map(|option_node| {
+            let probability = option_node
+                .attribute("probability")
+                .and_then(|probability| probability.parse().ok())
+                .ok_or_else(|| AttributeMissingError::new("option", "probability"))?;
+
+            Ok(PackOption {
+                probability,
+                items: pack_items(option_node)?,
+            })
+        })
+        .collect()
+}
+
+fn include(node: Node) -> Result<Include, AttributeMissingError> {
+    Ok(Include {
+        id: required_attribute(node, "id")?,
+        set: required_attribute(node, "set")?,
+        properties: properties(node),
+    })
+}
+
+fn properties(node: Node) -> Vec<(String, String)> {
+    node.children()
+        .filter(|child| child.is_element() && child.tag_name().name() == "property")
+        .filter_map(|property_node| {
+            let name = property_node.attribute("name")?;
+            let value = property_node.attribute("value").unwrap_or("");
+
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect()
+}
@@ -0,0 +1,426 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use super::{Card, CardSide, CardSize, Deck, Marker, Pack, PackItem, Set};
+
+const XML_DECLARATION: &str = r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>"#;
+
+impl Set {
+    /// Serialize into the same `set.xml` layout used by the OCTGN LotR repo.
+    pub fn to_xml(&self) -> String {
+        let mut atts = self
+            .attributes
+            .iter()
+            .filter(|(name, _)| name.starts_with("xmlns"))
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect::<Vec<(&str, &str)>>();
+        atts.push(("name", &self.name));
+        atts.push(("id", &self.id));
+        atts.push(("gameId", self.game.id()));
+        if let Some(game_version) = &self.game_version {
+            atts.push(("gameVersion", game_version));
+        }
+        if let Some(version) = &self.version {
+            atts.push(("version", version));
+        }
+        atts.extend(
+            self.attributes
+                .iter()
+                .filter(|(name, _)| !name.starts_with("xmlns"))
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        );
+
+        let mut xml = String::new();
+        xml.push_str(XML_DECLARATION);
+        xml.push('\n');
+        xml.push_str(&open_tag("set", &atts));
+        xml.push('\n');
+        if !self.packs.is_empty() {
+            xml.push_str(" <packaging>\n");
+            for pack in &self.packs {
+                write_pack(&mut xml, pack);
+            }
+            xml.push_str(" </packaging>\n");
+        }
+        if !self.markers.is_empty() {
+            xml.push_str(" <markers>\n");
+            for marker in &self.markers {
+                write_marker(&mut xml, marker);
+            }
+            xml.push_str(" </markers>\n");
+        }
+        xml.push_str(" <cards>\n");
+        for card in &self.cards {
+            write_card(&mut xml, card);
+        }
+        xml.push_str(" </cards>\n");
+        xml.push_str("</set>");
+
+        xml
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), std::io::Error> {
+        let mut file = File::create(path)?;
+        file.write_all(self.to_xml().as_bytes())?;
+
+        Ok(())
+    }
+}
+
+impl Deck {
+    /// Serialize into the `.o8d` layout the OCTGN client saves decks in.
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::new();
+        xml.push_str(XML_DECLARATION);
+        xml.push('\n');
+        xml.push_str(&open_tag("deck", &[("game", self.game.id())]));
+        xml.push('\n');
+        for section in &self.sections {
+            let shared = if section.shared { "True" } else { "False" };
+            let atts = [("name", section.name.as_str()), ("shared", shared)];
+
+            xml.push_str("  ");
+            if section.cards.is_empty() {
+                xml.push_str(&empty_tag("section", &atts));
+                xml.push('\n');
+                continue;
+            }
+
+            xml.push_str(&open_tag("section", &atts));
+            xml.push('\n');
+            for card in &section.cards {
+                let quantity = card.quantity.to_string();
+                let atts = [("qty", quantity.as_str()), ("id", card.id.as_str())];
+                xml.push_str("    ");
+                xml.push_str(&open_tag("card", &atts));
+                xml.push_str(&escape(&card.name));
+                xml.push_str("</card>\n");
+            }
+            xml.push_str("  </section>\n");
+        }
+        xml.push_str(&format!(
+            "  <notes><![CDATA[{}]]></notes>\n",
+            self.notes
+                .as_ref()
+                .map(|notes| notes.replace("]]>", "]]]]><![CDATA[>"))
+                .unwrap_or_default()
+        ));
+        xml.push_str("</deck>");
+
+        xml
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), std::io::Error> {
+        let mut file = File::create(path)?;
+        file.write_all(self.to_xml().as_bytes())?;
+
+        Ok(())
+    }
+}
+
+fn write_card(xml: &mut String, card: &Card) {
+    let mut atts = vec![("id", card.id.as_str()), ("name", card.name.as_str())];
+    if card.size != CardSize::Default {
+        atts.push(("size", card.size.as_str()));
+    }
+    atts.extend(
+        card.attributes
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str())),
+    );
+
+    xml.push_str("  ");
+    if card.properties.raw.is_empty() && card.alternates.is_empty() {
+        xml.push_str(&empty_tag("card", &atts));
+        xml.push('\n');
+        return;
+    }
+
+    xml.push_str(&open_tag("card", &atts));
+    xml.push('\n');
+    write_properties(xml, &card.properties.raw, 4);
+    for alternate in &card.alternates {
+        write_alternate(xml, alternate);
+    }
+    xml.push_str("  </card>\n");
+}
+
+fn write_alternate(xml: &mut String, side: &CardSide) {
+    let atts = [
+        ("name", side.name.as_str()),
+        ("type", side.alternate_type.as_str()),
+    ];
+
+    xml.push_str("    ");
+    if side.properties.raw.is_empty() {
+        xml.push_str(&empty_tag("alternate", &atts));
+        xml.push('\n');
+        return;
+    }
+
+    xml.push_str(&open_tag("alternate", &atts));
+    xml.push('\n');
+    write_properties(xml, &side.properties.raw, 6);
+    xml.push_str("    </alternate>\n");
+}
+
+fn write_pack(xml: &mut String, pack: &Pack) {
+    let atts = [("name", pack.name.as_str()), ("id", pack.id.as_str())];
+
+    xml.push_str("  ");
+    if pack.items.is_empty() {
+        xml.push_str(&empty_tag("pack", &atts));
+        xml.push('\n');
+        return;
+    }
+
+    xml.push_str(&open_tag("pack", &atts));
+    xml.push('\n');
+    write_pack_items(xml, &pack.items, 4);
+    xml.push_str("  </pack>\n");
+}
+
+fn write_pack_items(xml: &mut String, items: &[PackItem], indent: usize) {
+    let spaces = " ".repeat(indent);
+
+    for item in items {
+        xml.push_str(&spaces);
+        match item {
+            PackItem::Pick(pick) => {
+                let quantity = pick.quantity.to_string();
+                let mut atts = vec![("qty", quantity.as_str())];
+                if let Some(key) = &pick.key {
+                    atts.push(("key", key));
+                }
+                if let Some(value) = &pick.value {
+                    atts.push(("value", value));
+                }
+                write_element_with_properties(xml, "pick", &atts, &pick.properties, indent);
+            }
+            PackItem::Options(options) => {
+                xml.push_str("<options>\n");
+                for option in options {
+                    let probability = option.probability.to_string();
+                    let atts = [("probability", probability.as_str())];
+                    xml.push_str(&spaces);
+                    xml.push_str("  ");
+                    if option.items.is_empty() {
+                        xml.push_str(&empty_tag("option", &atts));
+                        xml.push('\n');
+                    } else {
+                        xml.push_str(&open_tag("option", &atts));
+                        xml.push('\n');
+                        write_pack_items(xml, &option.items, indent + 4);
+                        xml.push_str(&spaces);
+                        xml.push_str("  </option>\n");
+                    }
+                }
+                xml.push_str(&spaces);
+                xml.push_str("</options>\n");
+            }
+            PackItem::Include(include) => {
+                let atts = [("id", include.id.as_str()), ("set", include.set.as_str())];
+                write_element_with_properties(xml, "include", &atts, &include.properties, indent);
+            }
+        }
+    }
+}
+
+fn write_element_with_properties(
+    xml: &mut String,
+    tag: &str,
+    atts: &[(&str, &str)],
+    properties: &[(String, String)],
+    indent: usize,
+) {
+    if properties.is_empty() {
+        xml.push_str(&empty_tag(tag, atts));
+        xml.push('\n');
+        return;
+    }
+
+    xml.push_str(&open_tag(tag, atts));
+    xml.push('\n');
+    write_properties(xml, properties, indent + 2);
+    xml.push_str(&" ".repeat(indent));
+    xml.push_str(&format!("</{}>\n", tag));
+}
+
+fn write_marker(xml: &mut String, marker: &Marker) {
+    let atts = [("id", marker.id.as_str()), ("name", marker.name.as_str())];
+
+    xml.push_str("  ");
+    xml.push_str(&empty_tag("marker", &atts));
+    xml.push('\n');
+}
+
+fn write_properties(xml: &mut String, properties: &[(String, String)], indent: usize) {
+    let spaces = " ".repeat(indent);
+
+    for (name, value) in properties {
+        xml.push_str(&spaces);
+        xml.push_str(&empty_tag("property", &[("name", name), ("value", value)]));
+        xml.push('\n');
+    }
+}
+
+fn open_tag(tag: &str, atts: &[(&str, &str)]) -> String {
+    format!("<{}{}>", tag, format_attributes(atts))
+}
+
+fn empty_tag(tag: &str, atts: &[(&str, &str)]) -> String {
+    format!("<{}{}/>", tag, format_attributes(atts))
+}
+
+fn format_attributes(atts: &[(&str, &str)]) -> String {
+    atts.iter()
+        .map(|(name, value)| format!(" {}=\"{}\"", name, escape(value)))
+        .collect()
+}
+
+fn escape(value: &str) -> String {
+    value.chars().fold(String::new(), |mut acc, c| {
+        match c {
+            '&' => acc.push_str("&amp;"),
+            '<' => acc.push_str("&lt;"),
+            '>' => acc.push_str("&gt;"),
+            '"' => acc.push_str("&quot;"),
+            '\n' => acc.push_str("&#10;"),
+            '\r' => acc.push_str("&#13;"),
+            '\t' => acc.push_str("&#9;"),
+            _ => acc.push(c),
+        }
+
+        acc
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Read;
+
+    use roxmltree::Document;
+    use tempdir::TempDir;
+
+    use super::super::Game;
+    use crate::tests::load_octgn_set;
+
+    #[test]
+    fn test_to_xml_round_trip() {
+        let set = load_octgn_set();
+
+        let xml = std::fs::read_to_string("fixtures/set.xml").unwrap();
+        assert_eq!(set.to_xml(), xml);
+    }
+
+    #[test]
+    fn test_to_xml_escape() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<set name="Sets &amp; Pieces" id="e37145f0-8970-48d3-93bc-cef612226bda" gameId="a21af4e8-be4b-4cda-a6b6-534f9717391f">
+ <cards>
+  <card id="2b75792d-5873-4fc6-9272-d20dd517d36b" name="&quot;Quoted&quot; &lt;Card&gt;">
+    <property name="Text" value="Line one.&#10;Line two."/>
+  </card>
+  <card id="7d5f1011-3c36-4454-9efb-bf09553011d9" name="Empty" size="EncounterCard"/>
+ </cards>
+</set>"#;
+        let doc = Document::parse(xml).unwrap();
+        let set = Set::new(&doc, &Game::LOTR).unwrap();
+        assert_eq!(&set.name, "Sets & Pieces");
+        assert_eq!(&set.cards[0].name, "\"Quoted\" <Card>");
+
+        assert_eq!(set.to_xml(), xml);
+    }
+
+    #[test]
+    fn test_to_xml_packaging_and_markers() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<set name="The Wilds of Rhovanion" id="e37145f0-8970-48d3-93bc-cef612226bda" gameId="a21af4e8-be4b-4cda-a6b6-534f9717391f">
+ <packaging>
+  <pack name="Starter" id="0f4a6e1c-6a7b-4b6e-a2a7-3a0cfd5f1c1e">
+    <pick qty="unlimited" key="Type" value="Hero"/>
+    <options>
+      <option probability="0.25">
+        <pick qty="1" key="Sphere" value="Lore"/>
+      </option>
+      <option probability="0.75">
+        <pick qty="2">
+          <property name="Type" value="Ally"/>
+          <property name="Sphere" value="Spirit"/>
+        </pick>
+      </option>
+    </options>
+    <include id="2b75792d-5873-4fc6-9272-d20dd517d36b" set="e37145f0-8970-48d3-93bc-cef612226bda"/>
+  </pack>
+ </packaging>
+ <markers>
+  <marker id="2f0a5b1e-9a43-4f7b-8f3a-6a2c1d9e0b7a" name="Resource"/>
+ </markers>
+ <cards>
+ </cards>
+</set>"#;
+        let doc = Document::parse(xml).unwrap();
+        let set = Set::new(&doc, &Game::LOTR).unwrap();
+
+        assert_eq!(set.to_xml(), xml);
+    }
+
+    #[test]
+    fn test_deck_to_xml_round_trip() {
+        let deck = Deck::open(Path::new("fixtures/deck.o8d"), &Game::LOTR).unwrap();
+
+        let xml = deck.to_xml();
+        let doc = Document::parse(&xml).unwrap();
+        assert_eq!(Deck::new(&doc, &Game::LOTR).unwrap(), deck);
+    }
+
+    #[test]
+    fn test_deck_to_xml() {
+        let sets = vec![load_octgn_set()];
+        let cards = vec![
+            ("Brand son of Bain".to_string(), 1),
+            ("Redwater Sentry".to_string(), 3),
+        ];
+        let deck = Deck::from_cards(&cards, &sets, &Game::LOTR).unwrap();
+
+        let expected = r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<deck game="a21af4e8-be4b-4cda-a6b6-534f9717391f">
+  <section name="Hero" shared="False">
+    <card qty="1" id="2b75792d-5873-4fc6-9272-d20dd517d36b">Brand son of Bain</card>
+  </section>
+  <section name="Ally" shared="False">
+    <card qty="3" id="b69ae054-e422-43c7-a9b3-42f334ab61a5">Redwater Sentry</card>
+  </section>
+  <section name="Attachment" shared="False"/>
+  <section name="Event" shared="False"/>
+  <section name="Side Quest" shared="False"/>
+  <section name="Sideboard" shared="False"/>
+  <section name="Quest" shared="True"/>
+  <section name="Encounter" shared="True"/>
+  <section name="Special" shared="True"/>
+  <notes><![CDATA[]]></notes>
+</deck>"#;
+        assert_eq!(deck.to_xml(), expected);
+    }
+
+    #[test]
+    fn test_write() {
+        let tmp_dir = TempDir::new("lotr-test").unwrap();
+        let set = load_octgn_set();
+
+        let path = tmp_dir.path().join("set.xml");
+        set.write(&path).unwrap();
+
+        let mut written = String::new();
+        File::open(&path)
+            .unwrap()
+            .read_to_string(&mut written)
+            .unwrap();
+        let doc = Document::parse(&written).unwrap();
+        let written_set = Set::new(&doc, &Game::LOTR).unwrap();
+        assert_eq!(written_set.cards, set.cards);
+    }
+}
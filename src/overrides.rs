@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use serde_derive::*;
+
+use crate::Error;
+
+/// What to use for one OCTGN card instead of matching it.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CardOverride {
+    /// The title of the Hall of Beorn card to use, from the same set.
+    pub title: Option<String>,
+    /// An image URL or local image path to use, without looking at Hall of Beorn at all.
+    pub image: Option<String>,
+}
+
+/// A card override that says neither which card to use nor which image, so it would leave the card
+/// without an image.
+#[derive(Debug, Eq, PartialEq)]
+pub struct EmptyOverrideError {
+    /// The card's GUID, or its set and name.
+    pub card: String,
+}
+
+impl std::error::Error for EmptyOverrideError {}
+
+impl fmt::Display for EmptyOverrideError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "The override for '{}' has neither a title nor an image.",
+            self.card
+        )
+    }
+}
+
+/// Fixes for cards and sets the matching gets wrong, kept by hand in a TOML or JSON file:
+///
+/// ```toml
+/// [cards]
+/// "42a5a608-0699-4cd5-b69d-f7c3413cd5cd" = { title = "Fire-drake" }
+///
+/// [set_cards."The Wilds of Rhovanion"]
+/// "Woodman Village" = { image = "/home/me/woodmen-village.jpg" }
+///
+/// [sets]
+/// "The Voice of Isengard" = "Voice of Isengard"
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Overrides {
+    /// Cards by OCTGN GUID.
+    #[serde(default)]
+    pub cards: HashMap<String, CardOverride>,
+    /// Cards by OCTGN set name, then OCTGN card name.
+    #[serde(default)]
+    pub set_cards: HashMap<String, HashMap<String, CardOverride>>,
+    /// OCTGN set names by the Hall of Beorn set name they go with.
+    #[serde(default)]
+    pub sets: HashMap<String, String>,
+}
+
+impl Overrides {
+    /// Read overrides from a `.json` file, or from TOML for any other extension.
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let contents = std::fs::read_to_string(path)?;
+        let overrides: Overrides = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => {
+                serde_json::from_str(&contents).map_err(|err| Error::Config(Box::new(err)))?
+            }
+            _ => toml::from_str(&contents).map_err(|err| Error::Config(Box::new(err)))?,
+        };
+        overrides.check()?;
+
+        Ok(overrides)
+    }
+
+    /// Every card override has to name a title or an image.
+    fn check(&self) -> Result<(), Error> {
+        let by_id = self.cards.iter().map(|(id, card)| (id.to_string(), card));
+        let by_name = self.set_cards.iter().flat_map(|(set_name, cards)| {
+            cards
+                .iter()
+                .map(move |(name, card)| (format!("{}: {}", set_name, name), card))
+        });
+        let mut empty = by_id
+            .chain(by_name)
+            .filter(|(_, card)| card.title.is_none() && card.image.is_none())
+            .map(|(card, _)| card)
+            .collect::<Vec<String>>();
+        // the same error for the same file, whatever order the maps come back in
+        empty.sort();
+
+        match empty.into_iter().next() {
+            Some(card) => Err(Error::Config(Box::new(EmptyOverrideError { card }))),
+            None => Ok(()),
+        }
+    }
+
+    /// The override for a card, by its GUID first and then by its set and name. `id` is `None`
+    /// for a different card printed on the back of another, which only has a name.
+    pub fn card(&self, set_name: &str, id: Option<&str>, name: &str) -> Option<&CardOverride> {
+        id.and_then(|id| self.cards.get(id)).or_else(|| {
+            self.set_cards
+                .get(set_name)
+                .and_then(|cards| cards.get(name))
+        })
+    }
+
+    /// The OCTGN set name pinned to a Hall of Beorn set.
+    pub fn octgn_set(&self, hob_set_name: &str) -> Option<&str> {
+        self.sets.get(hob_set_name).map(|name| name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempdir::TempDir;
+
+    const TOML: &str = r#"
+[cards]
+"42a5a608-0699-4cd5-b69d-f7c3413cd5cd" = { title = "Fire-drake" }
+
+[set_cards."The Wilds of Rhovanion"]
+"Woodman Village" = { image = "woodmen-village.jpg" }
+
+[sets]
+"The Voice of Isengard" = "Voice of Isengard"
+"#;
+
+    #[test]
+    fn test_open_toml() {
+        let tmp_dir = TempDir::new("overrides").unwrap();
+        let path = tmp_dir.path().join("overrides.toml");
+        std::fs::write(&path, TOML).unwrap();
+
+        let overrides = Overrides::open(&path).unwrap();
+        let set = "The Wilds of Rhovanion";
+        let fire_drake = overrides
+            .card(
+                set,
+                Some("42a5a608-0699-4cd5-b69d-f7c3413cd5cd"),
+                "Fire Drake",
+            )
+            .unwrap();
+        assert_eq!(fire_drake.title.as_ref().unwrap(), "Fire-drake");
+        let woodman_village = overrides.card(set, None, "Woodman Village").unwrap();
+        assert_eq!(
+            woodman_village.image.as_ref().unwrap(),
+            "woodmen-village.jpg"
+        );
+        assert!(overrides
+            .card("Core Set", None, "Woodman Village")
+            .is_none());
+        assert_eq!(
+            overrides.octgn_set("The Voice of Isengard"),
+            Some("Voice of Isengard")
+        );
+    }
+
+    #[test]
+    fn test_open_json() {
+        let tmp_dir = TempDir::new("overrides").unwrap();
+        let toml_path = tmp_dir.path().join("overrides.toml");
+        std::fs::write(&toml_path, TOML).unwrap();
+        let json_path = tmp_dir.path().join("overrides.json");
+        let overrides = Overrides::open(&toml_path).unwrap();
+        std::fs::write(&json_path, serde_json::to_string(&overrides).unwrap()).unwrap();
+
+        assert_eq!(Overrides::open(&json_path).unwrap(), overrides);
+    }
+
+    #[test]
+    fn test_open_empty_override() {
+        let tmp_dir = TempDir::new("overrides").unwrap();
+        let path = tmp_dir.path().join("overrides.toml");
+        std::fs::write(
+            &path,
+            "[cards]\n\"42a5a608-0699-4cd5-b69d-f7c3413cd5cd\" = {}\n",
+        )
+        .unwrap();
+
+        match Overrides::open(&path) {
+            Err(Error::Config(err)) => assert_eq!(
+                err.to_string(),
+                "The override for '42a5a608-0699-4cd5-b69d-f7c3413cd5cd' has neither a title nor an image."
+            ),
+            _ => panic!("expected the empty override to be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_open_invalid() {
+        let tmp_dir = TempDir::new("overrides").unwrap();
+        let path = tmp_dir.path().join("overrides.toml");
+        std::fs::write(&path, "[cards\n").unwrap();
+
+        match Overrides::open(&path) {
+            Err(err @ Error::Config(_)) => assert_eq!(err.exit_code(), 13),
+            _ => panic!("expected the overrides to be invalid"),
+        }
+    }
+}
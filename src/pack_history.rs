@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde_derive::*;
+
+use crate::{octgn, Error, GitCache};
+
+/// What an `.o8c` was built from.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PackRecord {
+    /// The OCTGN repo commit the set was packed at.
+    pub commit: String,
+    /// SHA-1 of the set.xml that was packed.
+    pub xml_hash: String,
+}
+
+/// The last pack of every set, by OCTGN set id, so unchanged sets don't have to be packed again.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PackHistory {
+    pub packs: HashMap<String, PackRecord>,
+}
+
+impl PackHistory {
+    /// Read the history at `path`, or start an empty one if there isn't one yet.
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        if !path.is_file() {
+            return Ok(PackHistory::default());
+        }
+        let file = File::open(path)?;
+
+        serde_json::from_reader(file).map_err(|err| Error::Config(Box::new(err)))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = File::create(path)?;
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::from)?;
+        file.write_all(json.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Remember that `set` was just packed at `commit`.
+    pub fn record(&mut self, set: &octgn::Set, commit: &str) -> Result<(), Error> {
+        let xml_hash = match &set.path {
+            Some(path) => xml_hash(path)?,
+            None => return Ok(()),
+        };
+        self.packs.insert(
+            set.id.clone(),
+            PackRecord {
+                commit: commit.to_string(),
+                xml_hash,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// The sets that have never been packed, or whose set.xml or set folder changed since they
+    /// last were.
+    pub fn changed<'a>(
+        &self,
+        sets: &[&'a octgn::Set],
+        git_cache: &GitCache,
+    ) -> Result<Vec<&'a octgn::Set>, Error> {
+        // most sets were packed at the same few commits, so only diff each of those once
+        let mut changed_dirs: HashMap<&str, Option<Vec<PathBuf>>> = HashMap::new();
+        let mut changed = vec![];
+        for set in sets {
+            let (record, path) = match (self.packs.get(&set.id), &set.path) {
+                (Some(record), Some(path)) => (record, path),
+                _ => {
+                    changed.push(*set);
+                    continue;
+                }
+            };
+            if xml_hash(path)? != record.xml_hash {
+                changed.push(*set);
+                continue;
+            }
+
+            // a commit that's gone, say after a force push, can only be checked by its hash
+            let dirs = changed_dirs
+                .entry(&record.commit)
+                .or_insert_with(|| git_cache.changed_set_dirs(&record.commit).ok());
+            let set_dir = path.parent();
+            if let Some(dirs) = dirs {
+                if dirs.iter().any(|dir| Some(dir.as_path()) == set_dir) {
+                    changed.push(*set);
+                }
+            }
+        }
+
+        Ok(changed)
+    }
+}
+
+fn xml_hash(path: &Path) -> Result<String, Error> {
+    let xml = std::fs::read(path)?;
+
+    Ok(sha1::Sha1::from(xml).digest().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tests::load_octgn_set;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_record() {
+        let tmp_dir = TempDir::new("packs").unwrap();
+        let set_path = tmp_dir.path().join("set.xml");
+        std::fs::copy("fixtures/set.xml", &set_path).unwrap();
+        let mut set = load_octgn_set();
+        set.path = Some(set_path.clone());
+        let history_path = tmp_dir.path().join("packs.json");
+
+        let mut history = PackHistory::open(&history_path).unwrap();
+        assert!(history.packs.is_empty());
+        history.record(&set, "abc123").unwrap();
+        history.save(&history_path).unwrap();
+
+        let history = PackHistory::open(&history_path).unwrap();
+        let record = history.packs.get(&set.id).unwrap();
+        assert_eq!(record.commit, "abc123");
+        assert_eq!(record.xml_hash, xml_hash(&set_path).unwrap());
+    }
+
+    #[test]
+    fn test_open_corrupt() {
+        let tmp_dir = TempDir::new("packs").unwrap();
+        let history_path = tmp_dir.path().join("packs.json");
+        std::fs::write(&history_path, "{\"packs\": {").unwrap();
+
+        match PackHistory::open(&history_path) {
+            Err(err @ Error::Config(_)) => assert_eq!(err.exit_code(), 13),
+            _ => panic!("expected the pack history to be corrupt"),
+        }
+    }
+
+    #[test]
+    fn test_changed() {
+        let tmp_dir = TempDir::new("packs").unwrap();
+        let set_path = tmp_dir.path().join("set.xml");
+        std::fs::copy("fixtures/set.xml", &set_path).unwrap();
+        let mut set = load_octgn_set();
+        set.path = Some(set_path.clone());
+        // no repo, so only the set.xml hashes can be compared
+        let git_cache = GitCache::new(String::new(), tmp_dir.path());
+
+        let mut history = PackHistory::default();
+        assert_eq!(history.changed(&[&set], &git_cache).unwrap().len(), 1);
+
+        history.record(&set, "abc123").unwrap();
+        assert!(history.changed(&[&set], &git_cache).unwrap().is_empty());
+
+        let xml = std::fs::read_to_string(&set_path).unwrap();
+        std::fs::write(
+            &set_path,
+            xml.replace("Brand son of Bain", "Bard son of Brand"),
+        )
+        .unwrap();
+        assert_eq!(history.changed(&[&set], &git_cache).unwrap().len(), 1);
+    }
+}
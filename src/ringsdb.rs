@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use serde_derive::*;
+
+#[cfg(not(test))]
+const RINGSDB_URL: &str = "https://ringsdb.com";
+#[cfg(test)]
+const RINGSDB_URL: &str = mockito::SERVER_URL;
+
+#[derive(Serialize, Deserialize)]
+pub struct Card {
+    pub code: String,
+    pub name: String,
+    pub pack_name: String,
+    pub type_name: String,
+    pub position: u32,
+}
+
+impl Card {
+    pub fn fetch_all() -> Result<Vec<Card>, reqwest::Error> {
+        let cards: Vec<Card> = reqwest::Client::new()
+            .get(&format!("{}/api/public/cards/", RINGSDB_URL))
+            .send()?
+            .json()?;
+
+        Ok(cards)
+    }
+}
+
+/// A deck as exported by RingsDB's JSON API. Card codes map to the number of copies.
+#[derive(Serialize, Deserialize)]
+pub struct Deck {
+    pub name: String,
+    pub slots: HashMap<String, u32>,
+    #[serde(default)]
+    pub sideslots: HashMap<String, u32>,
+}
+
+/// A card from a RingsDB deck, before it's been matched to an OCTGN card.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeckEntry {
+    pub name: String,
+    pub pack_name: String,
+    /// The card's number in its pack. Text exports don't include it.
+    pub number: Option<u32>,
+    pub quantity: u32,
+    pub sideboard: bool,
+}
+
+impl Deck {
+    /// Look up each card code in `cards`. Codes RingsDB doesn't know about are returned separately.
+    pub fn entries(&self, cards: &[Card]) -> (Vec<DeckEntry>, Vec<String>) {
+        let cards_by_code = cards
+            .iter()
+            .map(|card| (card.code.as_str(), card))
+            .collect::<HashMap<&str, &Card>>();
+        let mut slots = self
+            .slots
+            .iter()
+            .map(|(code, quantity)| (code, quantity, false))
+            .chain(
+                self.sideslots
+                    .iter()
+                    .map(|(code, quantity)| (code, quantity, true)),
+            )
+            .collect::<Vec<(&String, &u32, bool)>>();
+        slots.sort();
+
+        let mut entries = vec![];
+        let mut unknown = vec![];
+        for (code, quantity, sideboard) in slots {
+            match cards_by_code.get(code.as_str()) {
+                Some(card) => entries.push(DeckEntry {
+                    name: card.name.to_string(),
+                    pack_name: card.pack_name.to_string(),
+                    number: Some(card.position),
+                    quantity: *quantity,
+                    sideboard,
+                }),
+                None => unknown.push(code.to_string()),
+            }
+        }
+
+        (entries, unknown)
+    }
+}
+
+/// Parse RingsDB's plain text export, where each card is a line like
+/// "3x Redwater Sentry (The Wilds of Rhovanion)" and sideboard cards follow a "Sideboard" line.
+pub fn parse_text(text: &str) -> Vec<DeckEntry> {
+    let mut sideboard = false;
+
+    text.lines()
+        .map(|line| line.trim())
+        .filter_map(|line| {
+            if line.to_lowercase().starts_with("sideboard") {
+                sideboard = true;
+                return None;
+            }
+
+            let (quantity, card) = match line.find("x ") {
+                Some(index) => match line[..index].parse::<u32>() {
+                    Ok(quantity) => (quantity, &line[index + 2..]),
+                    Err(_) => (1, line),
+                },
+                None => (1, line),
+            };
+            if !card.ends_with(')') {
+                return None;
+            }
+            let open = card.rfind(" (")?;
+            let name = card[..open].trim();
+            // skip headers like "Hero: (3)"
+            if name.is_empty() || name.ends_with(':') {
+                return None;
+            }
+
+            Some(DeckEntry {
+                name: name.to_string(),
+                pack_name: card[open + 2..card.len() - 1].to_string(),
+                number: None,
+                quantity,
+                sideboard,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::File;
+    use std::io::Read;
+
+    use crate::tests::mocks::ringsdb as mocks;
+
+    #[test]
+    fn test_card_fetch_all() {
+        let _m = mocks::cards();
+        let result = Card::fetch_all();
+        assert!(result.is_ok());
+
+        let cards = result.unwrap();
+        assert_eq!(cards.len(), 6);
+        assert_eq!(&cards[0].name, "Brand son of Bain");
+        assert_eq!(cards[0].position, 1);
+    }
+
+    #[test]
+    fn test_deck_entries() {
+        let _m = mocks::cards();
+        let cards = Card::fetch_all().unwrap();
+        let file = File::open("fixtures/ringsdb/deck.json").unwrap();
+        let deck: Deck = serde_json::from_reader(file).unwrap();
+        assert_eq!(&deck.name, "Dale Starter");
+
+        let (entries, unknown) = deck.entries(&cards);
+        assert!(unknown.is_empty());
+        assert_eq!(entries.len(), 6);
+        assert_eq!(
+            entries[0],
+            DeckEntry {
+                name: "Aragorn".to_string(),
+                pack_name: "Core Set".to_string(),
+                number: Some(1),
+                quantity: 1,
+                sideboard: false,
+            }
+        );
+        let bow = entries
+            .iter()
+            .find(|entry| entry.name == "Bow of Yew")
+            .unwrap();
+        assert!(bow.sideboard);
+    }
+
+    #[test]
+    fn test_parse_text() {
+        let mut file = File::open("fixtures/ringsdb/deck.txt").unwrap();
+        let mut text = String::new();
+        file.read_to_string(&mut text).unwrap();
+
+        let entries = parse_text(&text);
+        assert_eq!(entries.len(), 6);
+        assert_eq!(
+            entries[2],
+            DeckEntry {
+                name: "Redwater Sentry".to_string(),
+                pack_name: "The Wilds of Rhovanion".to_string(),
+                number: None,
+                quantity: 3,
+                sideboard: false,
+            }
+        );
+        assert!(!entries[4].sideboard);
+        assert!(entries[5].sideboard);
+        assert_eq!(&entries[5].name, "Bow of Yew");
+    }
+}
@@ -0,0 +1,41 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread::JoinHandle;
+
+/// A server of its own that answers the first `failures` requests with a 503 and the next one
+/// with `body`, then stops. Returns its URL and the thread serving it, which gives back how many
+/// requests it answered.
+pub fn server(failures: usize, body: &'static str) -> (String, JoinHandle<usize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    let handle = std::thread::spawn(move || {
+        let mut hits = 0;
+        for stream in listener.incoming().take(failures + 1) {
+            let mut stream = stream.unwrap();
+            // read up to the blank line that ends the request's headers
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+
+            let response = if hits < failures {
+                "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_string()
+            } else {
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            };
+            stream.write_all(response.as_bytes()).unwrap();
+            hits += 1;
+        }
+
+        hits
+    });
+
+    (url, handle)
+}
@@ -1 +1,3 @@
+pub mod flaky;
 pub mod hall_of_beorn;
+pub mod ringsdb;
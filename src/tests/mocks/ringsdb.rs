@@ -0,0 +1,17 @@
+use std::fs::File;
+use std::io::Read;
+
+use mockito::{mock, Mock};
+
+pub fn cards() -> Result<Mock, std::io::Error> {
+    let mut file = File::open("fixtures/ringsdb/cards.json")?;
+    let mut body = String::new();
+
+    file.read_to_string(&mut body)?;
+    let m = mock("GET", "/api/public/cards/")
+        .with_header("content-type", "application/json")
+        .with_body(body)
+        .create();
+
+    Ok(m)
+}